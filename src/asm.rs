@@ -0,0 +1,273 @@
+use std::collections::HashMap;
+
+// Two pass assembler for the syntax printed by `chip8 disasm`:
+//
+//   ; comments start with a semicolon
+//   start:  LD V0, 0x05      ; numbers are decimal, 0x/# hex or 0b binary
+//           CALL draw        ; labels can be used wherever an address is expected
+//           JP start
+//   draw:   DRW V0, V1, 5
+//           RET
+//   sprite: DB 0xF0, 0x90, 0xF0
+//           DW 0x1234
+//
+// `origin` is the address the program will be loaded at.
+pub fn assemble(source: &str, origin: usize) -> Result<Vec<u8>, String> {
+    let lines: Vec<Line> = source
+        .lines()
+        .enumerate()
+        .map(|(number, text)| Line::parse(number + 1, text))
+        .collect::<Result<_, _>>()?;
+
+    // first pass: label addresses
+    let mut labels = HashMap::new();
+    let mut address = origin;
+    for line in &lines {
+        if let Some(label) = &line.label {
+            if labels.insert(label.to_uppercase(), address).is_some() {
+                return Err(format!(
+                    "line {}: label `{}` defined twice",
+                    line.number, label
+                ));
+            }
+        }
+        address += line.size();
+    }
+
+    // second pass: encode
+    let mut output = Vec::new();
+    for line in &lines {
+        line.encode(&labels, &mut output)
+            .map_err(|e| format!("line {}: {}", line.number, e))?;
+    }
+    Ok(output)
+}
+
+struct Line {
+    number: usize,
+    label: Option<String>,
+    mnemonic: Option<String>,
+    operands: Vec<String>,
+}
+
+impl Line {
+    fn parse(number: usize, text: &str) -> Result<Self, String> {
+        let mut text = text.split(';').next().unwrap().trim();
+        let mut label = None;
+        if let Some((name, rest)) = text.split_once(':') {
+            let name = name.trim();
+            if name.is_empty() || !name.chars().all(|c| c.is_alphanumeric() || c == '_') {
+                return Err(format!("line {}: invalid label `{}`", number, name));
+            }
+            label = Some(name.to_string());
+            text = rest.trim();
+        }
+        let (mnemonic, operands) = match text.split_once(char::is_whitespace) {
+            Some((mnemonic, rest)) => (
+                Some(mnemonic.to_uppercase()),
+                rest.split(',').map(|o| o.trim().to_string()).collect(),
+            ),
+            None if text.is_empty() => (None, Vec::new()),
+            None => (Some(text.to_uppercase()), Vec::new()),
+        };
+        Ok(Self {
+            number,
+            label,
+            mnemonic,
+            operands,
+        })
+    }
+
+    fn size(&self) -> usize {
+        match self.mnemonic.as_deref() {
+            None => 0,
+            Some("DB") => self.operands.len(),
+            Some("DW") => self.operands.len() * 2,
+            Some(_) => 2,
+        }
+    }
+
+    fn encode(&self, labels: &HashMap<String, usize>, output: &mut Vec<u8>) -> Result<(), String> {
+        let mnemonic = match &self.mnemonic {
+            Some(mnemonic) => mnemonic.as_str(),
+            None => return Ok(()),
+        };
+        let operands: Vec<Operand> = self
+            .operands
+            .iter()
+            .map(|text| Operand::parse(text, labels))
+            .collect::<Result<_, _>>()?;
+
+        if mnemonic == "DB" || mnemonic == "DW" {
+            for operand in &operands {
+                match (mnemonic, operand) {
+                    ("DB", Operand::Number(value)) if *value <= 0xFF => output.push(*value as u8),
+                    ("DW", Operand::Number(value)) if *value <= 0xFFFF => {
+                        output.push((*value >> 8) as u8);
+                        output.push(*value as u8);
+                    }
+                    _ => return Err(format!("invalid {} value", mnemonic)),
+                }
+            }
+            return Ok(());
+        }
+
+        let opcode = encode(mnemonic, &operands)?;
+        output.push((opcode >> 8) as u8);
+        output.push(opcode as u8);
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+enum Operand {
+    Register(u16), // V0..VF
+    Number(usize), // literal or label address
+    I,
+    IndirectI, // [I]
+    Dt,
+    St,
+    K,
+    F,
+    B,
+    Audio, // xo-chip pattern buffer
+    Pitch,
+}
+
+impl Operand {
+    fn parse(text: &str, labels: &HashMap<String, usize>) -> Result<Self, String> {
+        let upper = text.to_uppercase();
+        let operand = match upper.as_str() {
+            "I" => Operand::I,
+            "[I]" => Operand::IndirectI,
+            "DT" => Operand::Dt,
+            "ST" => Operand::St,
+            "K" => Operand::K,
+            "F" => Operand::F,
+            "B" => Operand::B,
+            "AUDIO" => Operand::Audio,
+            "PITCH" => Operand::Pitch,
+            _ if upper.len() == 2 && upper.starts_with('V') => {
+                let register = u16::from_str_radix(&upper[1..], 16)
+                    .map_err(|_| format!("invalid register `{}`", text))?;
+                Operand::Register(register)
+            }
+            _ => match parse_number(&upper) {
+                Some(value) => Operand::Number(value),
+                None => match labels.get(&upper) {
+                    Some(address) => Operand::Number(*address),
+                    None => return Err(format!("unknown label or value `{}`", text)),
+                },
+            },
+        };
+        Ok(operand)
+    }
+}
+
+fn parse_number(text: &str) -> Option<usize> {
+    if let Some(hex) = text.strip_prefix("0X").or_else(|| text.strip_prefix('#')) {
+        usize::from_str_radix(hex, 16).ok()
+    } else if let Some(binary) = text.strip_prefix("0B") {
+        usize::from_str_radix(binary, 2).ok()
+    } else {
+        text.parse().ok()
+    }
+}
+
+fn encode(mnemonic: &str, operands: &[Operand]) -> Result<u16, String> {
+    use Operand::*;
+
+    let address = |value: usize| -> Result<u16, String> {
+        if value > 0xFFF {
+            return Err(format!("address {:#X} does not fit in 12 bits", value));
+        }
+        Ok(value as u16)
+    };
+    let byte = |value: usize| -> Result<u16, String> {
+        if value > 0xFF {
+            return Err(format!("value {:#X} does not fit in a byte", value));
+        }
+        Ok(value as u16)
+    };
+
+    let opcode = match (mnemonic, operands) {
+        ("CLS", []) => 0x00E0,
+        ("RET", []) => 0x00EE,
+        ("SYS", [Number(nnn)]) => address(*nnn)?,
+        ("JP", [Number(nnn)]) => 0x1000 | address(*nnn)?,
+        ("JP", [Register(0), Number(nnn)]) => 0xB000 | address(*nnn)?,
+        ("CALL", [Number(nnn)]) => 0x2000 | address(*nnn)?,
+        ("SE", [Register(x), Number(kk)]) => 0x3000 | x << 8 | byte(*kk)?,
+        ("SNE", [Register(x), Number(kk)]) => 0x4000 | x << 8 | byte(*kk)?,
+        ("SE", [Register(x), Register(y)]) => 0x5000 | x << 8 | y << 4,
+        ("LD", [Register(x), Number(kk)]) => 0x6000 | x << 8 | byte(*kk)?,
+        ("ADD", [Register(x), Number(kk)]) => 0x7000 | x << 8 | byte(*kk)?,
+        ("LD", [Register(x), Register(y)]) => 0x8000 | x << 8 | y << 4,
+        ("OR", [Register(x), Register(y)]) => 0x8001 | x << 8 | y << 4,
+        ("AND", [Register(x), Register(y)]) => 0x8002 | x << 8 | y << 4,
+        ("XOR", [Register(x), Register(y)]) => 0x8003 | x << 8 | y << 4,
+        ("ADD", [Register(x), Register(y)]) => 0x8004 | x << 8 | y << 4,
+        ("SUB", [Register(x), Register(y)]) => 0x8005 | x << 8 | y << 4,
+        ("SHR", [Register(x), Register(y)]) => 0x8006 | x << 8 | y << 4,
+        ("SHR", [Register(x)]) => 0x8006 | x << 8 | x << 4,
+        ("SUBN", [Register(x), Register(y)]) => 0x8007 | x << 8 | y << 4,
+        ("SHL", [Register(x), Register(y)]) => 0x800E | x << 8 | y << 4,
+        ("SHL", [Register(x)]) => 0x800E | x << 8 | x << 4,
+        ("SNE", [Register(x), Register(y)]) => 0x9000 | x << 8 | y << 4,
+        ("LD", [I, Number(nnn)]) => 0xA000 | address(*nnn)?,
+        ("RND", [Register(x), Number(kk)]) => 0xC000 | x << 8 | byte(*kk)?,
+        ("DRW", [Register(x), Register(y), Number(n)]) if *n <= 0xF => {
+            0xD000 | x << 8 | y << 4 | *n as u16
+        }
+        ("SKP", [Register(x)]) => 0xE09E | x << 8,
+        ("SKNP", [Register(x)]) => 0xE0A1 | x << 8,
+        ("LD", [Register(x), Dt]) => 0xF007 | x << 8,
+        ("LD", [Register(x), K]) => 0xF00A | x << 8,
+        ("LD", [Dt, Register(x)]) => 0xF015 | x << 8,
+        ("LD", [St, Register(x)]) => 0xF018 | x << 8,
+        ("ADD", [I, Register(x)]) => 0xF01E | x << 8,
+        ("LD", [F, Register(x)]) => 0xF029 | x << 8,
+        ("LD", [B, Register(x)]) => 0xF033 | x << 8,
+        ("LD", [Audio, IndirectI]) => 0xF002,
+        ("LD", [Pitch, Register(x)]) => 0xF03A | x << 8,
+        ("LD", [IndirectI, Register(x)]) => 0xF055 | x << 8,
+        ("LD", [Register(x), IndirectI]) => 0xF065 | x << 8,
+        _ => return Err(format!("invalid operands for {}: {:?}", mnemonic, operands)),
+    };
+    Ok(opcode)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chip::disassemble;
+
+    #[test]
+    fn disassembly_assembles_back_to_every_opcode() {
+        for opcode in 0..=0xFFFFu16 {
+            let text = disassemble(opcode);
+            assert_eq!(
+                assemble(&text, 0x200),
+                Ok(opcode.to_be_bytes().to_vec()),
+                "{:04X} `{}`",
+                opcode,
+                text
+            );
+        }
+    }
+
+    #[test]
+    fn resolves_labels_forward_and_back() {
+        let source = "start: CALL draw\n  JP start\ndraw: RET\n";
+        assert_eq!(
+            assemble(source, 0x200),
+            Ok(vec![0x22, 0x04, 0x12, 0x00, 0x00, 0xEE])
+        );
+    }
+
+    #[test]
+    fn reports_the_line_of_an_error() {
+        let error = assemble("CLS\nJP nowhere\n", 0x200).unwrap_err();
+        assert!(error.starts_with("line 2"), "{}", error);
+    }
+}
@@ -0,0 +1,291 @@
+use super::pattern::Pattern;
+use crate::chip::{SoundChange, SoundEvent};
+use std::f32::consts::PI;
+
+pub const WAVEFORMS: [&str; 4] = ["square", "triangle", "sine", "buzz"];
+
+// One period of a harsh, reedy buzz, closer to the small speakers of
+// the original machines than a clean square
+const BUZZ: [f32; 16] = [
+    1.0, 1.0, 0.9, 0.7, 0.2, -0.3, -0.5, -0.6, -0.6, -0.5, -0.4, -0.2, 0.1, 0.4, 0.7, 0.9,
+];
+
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub enum Waveform {
+    #[default]
+    Square,
+    Triangle,
+    Sine,
+    Buzz, // sampled, see BUZZ
+}
+
+impl Waveform {
+    pub fn parse(name: &str) -> Option<Self> {
+        let waveform = match name {
+            "square" => Waveform::Square,
+            "triangle" => Waveform::Triangle,
+            "sine" => Waveform::Sine,
+            "buzz" => Waveform::Buzz,
+            _ => return None,
+        };
+        Some(waveform)
+    }
+
+    // value at a phase from 0.0 to 1.0, between -1.0 and 1.0
+    fn sample(&self, phase: f32) -> f32 {
+        match self {
+            Waveform::Square => {
+                if phase <= 0.5 {
+                    1.0
+                } else {
+                    -1.0
+                }
+            }
+            Waveform::Triangle => 1.0 - 4.0 * (phase - 0.5).abs(),
+            Waveform::Sine => (phase * 2.0 * PI).sin(),
+            Waveform::Buzz => BUZZ[(phase * BUZZ.len() as f32) as usize % BUZZ.len()],
+        }
+    }
+}
+
+// Tone that sounds while the sound timer runs, switched on and off at
+// the sample matching the emulated time of each sound event. It fades
+// in and out over the attack and release times instead of starting and
+// stopping abruptly, which would click. Once an XO-CHIP program loads
+// an audio pattern, the pattern plays instead of the waveform.
+pub struct Beeper {
+    waveform: Waveform,
+    step: f32, // phase advance per sample
+    volume: f32,
+    attack: f32,  // level gained per sample
+    release: f32, // level lost per sample
+    phase: f32,   // 0.0 to 1.0
+    pattern: Pattern,
+    level: f32, // envelope, 0.0 to 1.0
+    on: bool,   // sound timer running
+    pub muted: bool,
+    samples_per_frame: f64,
+    owed: f64, // fraction of a sample carried to the next frame
+}
+
+impl Beeper {
+    // attack and release in milliseconds, 0 to switch at once
+    pub fn new(
+        waveform: Waveform,
+        tone: f32,
+        volume: f32,
+        attack: f32,
+        release: f32,
+        sample_rate: u32,
+    ) -> Self {
+        let per_sample = |ms: f32| {
+            let samples = ms / 1000.0 * sample_rate as f32;
+            if samples < 1.0 {
+                1.0
+            } else {
+                1.0 / samples
+            }
+        };
+        Self {
+            waveform,
+            step: tone / sample_rate as f32,
+            volume,
+            attack: per_sample(attack),
+            release: per_sample(release),
+            phase: 0.0,
+            pattern: Pattern::new(sample_rate),
+            level: 0.0,
+            on: false,
+            muted: false,
+            samples_per_frame: sample_rate as f64 / 60.0,
+            owed: 0.0,
+        }
+    }
+
+    // A new program starts: the tone stops, fading out over the release,
+    // and the XO-CHIP pattern and pitch of the last one are forgotten.
+    pub fn reset(&mut self) {
+        self.on = false;
+        self.pattern.reset();
+    }
+
+    // one 60 Hz frame of samples, given the frame's sound events
+    pub fn frame(&mut self, events: &[SoundEvent]) -> Vec<f32> {
+        self.owed += self.samples_per_frame;
+        let count = self.owed as usize;
+        self.owed -= count as f64;
+
+        let mut events = events.iter().peekable();
+        let mut samples = Vec::with_capacity(count);
+        for i in 0..count {
+            while let Some(event) = events.next_if(|e| (e.position * count as f32) as usize <= i) {
+                self.apply(event.change);
+            }
+            self.level = if self.on && !self.muted {
+                (self.level + self.attack).min(1.0)
+            } else {
+                (self.level - self.release).max(0.0)
+            };
+            if self.level == 0.0 {
+                samples.push(0.0);
+                continue;
+            }
+            let value = if self.pattern.loaded() {
+                self.pattern.next()
+            } else {
+                let value = self.waveform.sample(self.phase);
+                self.phase = (self.phase + self.step) % 1.0;
+                value
+            };
+            samples.push(value * self.volume * self.level);
+        }
+        // events at the very end of the frame
+        for event in events {
+            self.apply(event.change);
+        }
+        samples
+    }
+
+    fn apply(&mut self, change: SoundChange) {
+        match change {
+            SoundChange::On => self.on = true,
+            SoundChange::Off => self.on = false,
+            SoundChange::Pattern(buffer) => self.pattern.load(buffer),
+            SoundChange::Pitch(pitch) => self.pattern.set_pitch(pitch),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(position: f32, change: SoundChange) -> SoundEvent {
+        SoundEvent {
+            frame: 0,
+            position,
+            change,
+        }
+    }
+
+    // a square at 0 Hz stays at 1.0, so samples show the envelope
+    fn flat(attack: f32, release: f32) -> Beeper {
+        Beeper::new(Waveform::Square, 0.0, 1.0, attack, release, 1000)
+    }
+
+    #[test]
+    fn every_listed_waveform_parses() {
+        for name in WAVEFORMS.iter() {
+            assert!(Waveform::parse(name).is_some(), "{}", name);
+        }
+        assert_eq!(Waveform::parse("saw"), None);
+    }
+
+    #[test]
+    fn waveform_shapes() {
+        let at = |waveform: Waveform, phase: f32| waveform.sample(phase);
+        assert_eq!(at(Waveform::Square, 0.25), 1.0);
+        assert_eq!(at(Waveform::Square, 0.75), -1.0);
+        assert_eq!(at(Waveform::Triangle, 0.0), -1.0);
+        assert_eq!(at(Waveform::Triangle, 0.5), 1.0);
+        assert!((at(Waveform::Sine, 0.25) - 1.0).abs() < 1e-6);
+        assert_eq!(at(Waveform::Buzz, 0.0), BUZZ[0]);
+        assert_eq!(at(Waveform::Buzz, 0.5), BUZZ[8]);
+    }
+
+    #[test]
+    fn silent_until_the_timer_starts() {
+        let mut beeper = Beeper::new(Waveform::Square, 440.0, 1.0, 0.0, 0.0, 44100);
+        let samples = beeper.frame(&[]);
+        assert_eq!(samples.len(), 735);
+        assert!(samples.iter().all(|sample| *sample == 0.0));
+    }
+
+    #[test]
+    fn starts_at_the_sample_of_its_event() {
+        let mut beeper = Beeper::new(Waveform::Square, 440.0, 1.0, 0.0, 0.0, 44100);
+        let samples = beeper.frame(&[event(0.5, SoundChange::On)]);
+        assert!(samples[..367].iter().all(|sample| *sample == 0.0));
+        assert_eq!(samples[367], 1.0);
+    }
+
+    #[test]
+    fn ramps_in_and_out_over_attack_and_release() {
+        let mut beeper = flat(10.0, 5.0);
+        let samples = beeper.frame(&[event(0.0, SoundChange::On)]);
+        for (i, sample) in samples[..10].iter().enumerate() {
+            assert!(
+                (sample - (i + 1) as f32 * 0.1).abs() < 1e-4,
+                "{:?}",
+                samples
+            );
+        }
+        assert_eq!(samples[10..], [1.0; 6]);
+
+        let samples = beeper.frame(&[event(0.0, SoundChange::Off)]);
+        for (i, sample) in samples[..5].iter().enumerate() {
+            assert!(
+                (sample - (4 - i) as f32 * 0.2).abs() < 1e-4,
+                "{:?}",
+                samples
+            );
+        }
+        assert!(samples[5..].iter().all(|sample| *sample == 0.0));
+    }
+
+    #[test]
+    fn switches_at_once_without_ramps() {
+        let mut beeper = flat(0.0, 0.0);
+        let samples = beeper.frame(&[event(0.0, SoundChange::On), event(0.5, SoundChange::Off)]);
+        assert_eq!(samples[..8], [1.0; 8]);
+        assert!(samples[8..].iter().all(|sample| *sample == 0.0));
+    }
+
+    #[test]
+    fn muting_fades_out_like_a_release() {
+        let mut beeper = flat(0.0, 5.0);
+        beeper.frame(&[event(0.0, SoundChange::On)]);
+        beeper.muted = true;
+        let samples = beeper.frame(&[]);
+        assert!((samples[0] - 0.8).abs() < 1e-4);
+        assert!(samples[4] < 1e-4);
+        assert!(samples[5..].iter().all(|sample| *sample == 0.0));
+    }
+
+    #[test]
+    fn a_loaded_pattern_replaces_the_waveform() {
+        // 4000 Hz, one pattern bit per sample
+        let mut beeper = Beeper::new(Waveform::Sine, 440.0, 0.5, 0.0, 0.0, 4000);
+        let mut buffer = [0; 16];
+        buffer[0] = 0b1100_0000;
+        let samples = beeper.frame(&[
+            event(0.0, SoundChange::Pattern(buffer)),
+            event(0.0, SoundChange::On),
+        ]);
+        assert_eq!(samples[..4], [0.5, 0.5, -0.5, -0.5]);
+    }
+
+    #[test]
+    fn reset_forgets_the_pattern_and_stops_the_tone() {
+        let mut beeper = flat(0.0, 0.0);
+        let samples = beeper.frame(&[
+            event(0.0, SoundChange::Pattern([0; 16])),
+            event(0.0, SoundChange::Pitch(0)),
+            event(0.0, SoundChange::On),
+        ]);
+        assert_eq!(samples[0], -1.0);
+        beeper.reset();
+        assert!(beeper.frame(&[]).iter().all(|sample| *sample == 0.0));
+        // the square wave again
+        let samples = beeper.frame(&[event(0.0, SoundChange::On)]);
+        assert_eq!(samples[..], [1.0; 17]);
+    }
+
+    #[test]
+    fn frames_carry_the_fraction_of_a_sample() {
+        // 1000 / 60 samples a frame
+        let mut beeper = flat(0.0, 0.0);
+        let lengths: Vec<usize> = (0..3).map(|_| beeper.frame(&[]).len()).collect();
+        assert_eq!(lengths, vec![16, 17, 17]);
+    }
+}
@@ -0,0 +1,70 @@
+mod beeper;
+mod pattern;
+mod tones;
+
+use crate::chip::SoundEvent;
+use crate::options::Options;
+pub use beeper::{Beeper, Waveform, WAVEFORMS};
+pub use tones::ToneLog;
+
+// Where the beeper's samples go: the sound card, a WAV file or a log.
+// Samples are mono, between -1.0 and 1.0.
+pub trait SampleSink {
+    fn sample_rate(&self) -> u32;
+    fn write(&mut self, samples: &[f32]) -> Result<(), String>;
+
+    // false for sinks that want tones to start and stop on frame edges
+    fn ramps(&self) -> bool {
+        true
+    }
+}
+
+// A beeper feeding a sink. Samples are produced in emulated time, one
+// frame's worth per call, so the output does not depend on how fast
+// the emulation runs.
+pub struct Audio<S: SampleSink> {
+    beeper: Beeper,
+    sink: S,
+}
+
+impl<S: SampleSink> Audio<S> {
+    pub fn new(sink: S, options: &Options) -> Self {
+        let (attack, release) = if sink.ramps() {
+            (options.attack, options.release)
+        } else {
+            (0.0, 0.0)
+        };
+        let beeper = Beeper::new(
+            options.waveform,
+            options.tone,
+            options.volume,
+            attack,
+            release,
+            sink.sample_rate(),
+        );
+        Self { beeper, sink }
+    }
+
+    pub fn frame(&mut self, events: &[SoundEvent]) -> Result<(), String> {
+        let samples = self.beeper.frame(events);
+        self.sink.write(&samples)
+    }
+
+    // a new program starts, see Beeper::reset
+    pub fn reset(&mut self) {
+        self.beeper.reset();
+    }
+
+    // muting fades the tone out like the end of a beep
+    pub fn set_muted(&mut self, muted: bool) {
+        self.beeper.muted = muted;
+    }
+
+    pub fn sink(&self) -> &S {
+        &self.sink
+    }
+
+    pub fn into_sink(self) -> S {
+        self.sink
+    }
+}
@@ -0,0 +1,138 @@
+// XO-CHIP sound: 128 one bit samples loaded by F002, looped at a rate
+// set by the pitch register, 4000 * 2 ^ ((pitch - 64) / 48) Hz
+const BITS: f64 = 128.0;
+
+pub struct Pattern {
+    buffer: Option<[u8; 16]>, // none until the program loads one
+    position: f64,            // in bits, 0.0 to 128.0
+    step: f64,                // bits per output sample
+    sample_rate: u32,
+}
+
+impl Pattern {
+    pub fn new(sample_rate: u32) -> Self {
+        let mut pattern = Self {
+            buffer: None,
+            position: 0.0,
+            step: 0.0,
+            sample_rate,
+        };
+        pattern.set_pitch(64);
+        pattern
+    }
+
+    // back to no pattern at the default pitch, as a new program starts
+    pub fn reset(&mut self) {
+        self.buffer = None;
+        self.position = 0.0;
+        self.set_pitch(64);
+    }
+
+    pub fn loaded(&self) -> bool {
+        self.buffer.is_some()
+    }
+
+    // the loop goes on from where it was, only the bits change
+    pub fn load(&mut self, buffer: [u8; 16]) {
+        self.buffer = Some(buffer);
+    }
+
+    pub fn set_pitch(&mut self, pitch: u8) {
+        let rate = 4000.0 * 2f64.powf((pitch as f64 - 64.0) / 48.0);
+        self.step = rate / self.sample_rate as f64;
+    }
+
+    // Next output sample, between -1.0 and 1.0. It averages the bits the
+    // sample spans rather than picking one, so high pitches played at a
+    // low sample rate do not alias into noise.
+    pub fn next(&mut self) -> f32 {
+        let buffer = match &self.buffer {
+            Some(buffer) => buffer,
+            None => return 0.0,
+        };
+        let end = self.position + self.step;
+        let mut sum = 0.0;
+        let mut at = self.position;
+        while at < end {
+            let next = (at.floor() + 1.0).min(end);
+            let bit = at as usize % BITS as usize;
+            let value = if buffer[bit / 8] >> (7 - bit % 8) & 1 == 1 {
+                1.0
+            } else {
+                -1.0
+            };
+            sum += value * (next - at);
+            at = next;
+        }
+        self.position = end % BITS;
+        (sum / self.step) as f32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // at 4000 Hz and the default pitch, one bit per sample
+    fn playing(buffer: [u8; 16]) -> Pattern {
+        let mut pattern = Pattern::new(4000);
+        pattern.load(buffer);
+        pattern
+    }
+
+    #[test]
+    fn pitch_sets_the_rate_from_4000_hz() {
+        let mut pattern = Pattern::new(4000);
+        assert_eq!(pattern.step, 1.0);
+        pattern.set_pitch(112); // an octave up
+        assert!((pattern.step - 2.0).abs() < 1e-9);
+        pattern.set_pitch(16); // an octave down
+        assert!((pattern.step - 0.5).abs() < 1e-9);
+    }
+
+    #[test]
+    fn silent_until_loaded() {
+        let mut pattern = Pattern::new(4000);
+        assert!(!pattern.loaded());
+        assert_eq!(pattern.next(), 0.0);
+    }
+
+    #[test]
+    fn plays_bits_most_significant_first_and_loops() {
+        let mut buffer = [0; 16];
+        buffer[0] = 0xF0;
+        let mut pattern = playing(buffer);
+        let samples: Vec<f32> = (0..132).map(|_| pattern.next()).collect();
+        assert_eq!(samples[..4], [1.0; 4]);
+        assert!(samples[4..128].iter().all(|sample| *sample == -1.0));
+        assert_eq!(samples[128..], [1.0; 4]);
+    }
+
+    #[test]
+    fn low_pitches_repeat_each_bit() {
+        let mut pattern = playing([0xAA; 16]);
+        pattern.set_pitch(16);
+        let samples: Vec<f32> = (0..6).map(|_| pattern.next()).collect();
+        assert_eq!(samples, vec![1.0, 1.0, -1.0, -1.0, 1.0, 1.0]);
+    }
+
+    #[test]
+    fn high_pitches_average_the_bits_spanned() {
+        let mut pattern = playing([0xAA; 16]);
+        pattern.set_pitch(112);
+        assert!((0..64).all(|_| pattern.next() == 0.0));
+    }
+
+    #[test]
+    fn a_new_buffer_plays_on_from_the_same_position() {
+        let mut pattern = playing([0x00; 16]);
+        for _ in 0..4 {
+            pattern.next();
+        }
+        let mut buffer = [0; 16];
+        buffer[0] = 0x08; // bit 4 only
+        pattern.load(buffer);
+        assert_eq!(pattern.next(), 1.0);
+        assert_eq!(pattern.next(), -1.0);
+    }
+}
@@ -0,0 +1,82 @@
+use super::SampleSink;
+
+// Spans of audible output counted in emulated frames, compared by
+// `chip8 test` against `<rom>.tones`. One line per span, start frame
+// and length:
+//
+//   120 30      a tone from frame 120 lasting 30 frames (0.5s)
+//
+// Expects one write per frame, as `Audio` does.
+#[derive(Default)]
+pub struct ToneLog {
+    frame: u64,
+    spans: Vec<(u64, u64)>,
+}
+
+impl SampleSink for ToneLog {
+    fn sample_rate(&self) -> u32 {
+        44100
+    }
+
+    fn write(&mut self, samples: &[f32]) -> Result<(), String> {
+        if samples.iter().any(|sample| *sample != 0.0) {
+            match self.spans.last_mut() {
+                Some((start, length)) if *start + *length == self.frame => *length += 1,
+                _ => self.spans.push((self.frame, 1)),
+            }
+        }
+        self.frame += 1;
+        Ok(())
+    }
+
+    fn ramps(&self) -> bool {
+        false
+    }
+}
+
+impl ToneLog {
+    pub fn to_text(&self) -> String {
+        self.spans
+            .iter()
+            .map(|(start, length)| format!("{} {}\n", start, length))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audio::Audio;
+    use crate::chip::{Cpu, Rom};
+    use crate::options::Options;
+
+    #[test]
+    fn joins_consecutive_audible_frames_into_spans() {
+        let mut log = ToneLog::default();
+        for frame in 0..8 {
+            let audible = (2..5).contains(&frame) || frame == 6;
+            log.write(&[0.0, if audible { 0.5 } else { 0.0 }]).unwrap();
+        }
+        assert_eq!(log.to_text(), "2 3\n6 1\n");
+    }
+
+    #[test]
+    fn sound_timer_sounds_for_its_frames() {
+        // LD V0, 30; LD ST, V0; JP 0x204
+        let data = vec![0x60, 0x1E, 0xF0, 0x18, 0x12, 0x04];
+        let options = Options::default();
+        let mut cpu = Cpu::new(&options);
+        cpu.load(&Rom {
+            data,
+            sha1: String::new(),
+        })
+        .unwrap();
+        let mut audio = Audio::new(ToneLog::default(), &options);
+        for _ in 0..60 {
+            cpu.run_frame().unwrap();
+            audio.frame(&cpu.sound_events()).unwrap();
+        }
+        // half a second of tone from the first frame
+        assert_eq!(audio.sink().to_text(), "0 30\n");
+    }
+}
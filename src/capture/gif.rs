@@ -0,0 +1,206 @@
+use crate::image::Image;
+use gif::{Encoder, Frame, Repeat};
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+
+// Most viewers treat shorter delays as 10 centiseconds
+const MIN_DELAY: u64 = 2;
+
+// Animated GIF fed one image per emulated frame. Identical frames are
+// merged into one with a longer delay, and a frame shown for less than
+// MIN_DELAY is replaced by the next one, so timing follows the 60 Hz
+// frame clock without relying on wall time.
+pub struct GifWriter {
+    path: PathBuf,
+    encoder: Option<Encoder<BufWriter<File>>>, // created with the first frame
+    size: (usize, usize),
+    pending: Option<(Image, u64)>, // image and the frame it appeared on
+    frames: u64,                   // emulated frames seen
+}
+
+impl GifWriter {
+    pub fn create(path: &Path) -> Self {
+        Self {
+            path: path.to_path_buf(),
+            encoder: None,
+            size: (0, 0),
+            pending: None,
+            frames: 0,
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn frame(&mut self, image: &Image) -> Result<(), String> {
+        let frame = self.frames;
+        self.frames += 1;
+
+        if self.encoder.is_none() {
+            let file = File::create(&self.path).map_err(|e| self.error(&e))?;
+            let (width, height) = (image.width as u16, image.height as u16);
+            let mut encoder = Encoder::new(BufWriter::new(file), width, height, &[])
+                .map_err(|e| self.error(&e))?;
+            encoder
+                .set_repeat(Repeat::Infinite)
+                .map_err(|e| self.error(&e))?;
+            self.encoder = Some(encoder);
+            self.size = (image.width, image.height);
+        }
+        // the gif keeps the size of its first frame, later ones are
+        // stretched to it
+        let resized;
+        let image = if (image.width, image.height) != self.size {
+            resized = image.resized(self.size.0, self.size.1);
+            &resized
+        } else {
+            image
+        };
+
+        let (pending, start) = match self.pending.take() {
+            Some(pending) => pending,
+            None => {
+                self.pending = Some((image.clone(), frame));
+                return Ok(());
+            }
+        };
+        let shown = centiseconds(frame) - centiseconds(start);
+        if pending == *image {
+            self.pending = Some((pending, start));
+        } else if shown < MIN_DELAY {
+            // too short to be shown, the new image takes its place
+            self.pending = Some((image.clone(), start));
+        } else {
+            self.write(&pending, shown)?;
+            self.pending = Some((image.clone(), frame));
+        }
+        Ok(())
+    }
+
+    // write the last frame and the trailer
+    pub fn finish(mut self) -> Result<(), String> {
+        if let Some((pending, start)) = self.pending.take() {
+            let delay = centiseconds(self.frames) - centiseconds(start);
+            self.write(&pending, delay.max(MIN_DELAY))?;
+        }
+        if let Some(encoder) = self.encoder.take() {
+            let mut writer = encoder.into_inner().map_err(|e| self.error(&e))?;
+            std::io::Write::flush(&mut writer).map_err(|e| self.error(&e))?;
+        }
+        Ok(())
+    }
+
+    fn write(&mut self, image: &Image, delay: u64) -> Result<(), String> {
+        let mut frame = encode(image);
+        frame.delay = delay.min(u16::MAX as u64) as u16;
+        let result = self.encoder.as_mut().unwrap().write_frame(&frame);
+        result.map_err(|e| self.error(&e))
+    }
+
+    fn error(&self, error: &dyn std::fmt::Display) -> String {
+        format!("{}: {}", self.path.display(), error)
+    }
+}
+
+// start of a 60 Hz frame in the gif's time unit
+fn centiseconds(frame: u64) -> u64 {
+    frame * 100 / 60
+}
+
+// Exact local palette when the image has at most 256 colours, which
+// is the usual case, quantized otherwise
+fn encode(image: &Image) -> Frame<'static> {
+    let (width, height) = (image.width as u16, image.height as u16);
+    let mut indices = HashMap::new();
+    let mut palette = Vec::new();
+    let mut buffer = Vec::with_capacity(image.pixels.len());
+    for color in &image.pixels {
+        let next = indices.len();
+        let index = *indices.entry((color.r, color.g, color.b)).or_insert(next);
+        if index == next {
+            if next == 256 {
+                return Frame::from_rgb_speed(width, height, &image.to_rgb(), 10);
+            }
+            palette.extend_from_slice(&[color.r, color.g, color.b]);
+        }
+        buffer.push(index as u8);
+    }
+    Frame {
+        width,
+        height,
+        palette: Some(palette),
+        buffer: Cow::Owned(buffer),
+        ..Frame::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sdl2::pixels::Color;
+
+    fn delays(path: &Path) -> Vec<u16> {
+        let file = File::open(path).unwrap();
+        let mut decoder = gif::DecodeOptions::new().read_info(file).unwrap();
+        let mut delays = Vec::new();
+        while let Some(frame) = decoder.read_next_frame().unwrap() {
+            delays.push(frame.delay);
+        }
+        delays
+    }
+
+    #[test]
+    fn frames_follow_the_60hz_clock_in_centiseconds() {
+        assert_eq!(centiseconds(0), 0);
+        assert_eq!(centiseconds(3), 5);
+        assert_eq!(centiseconds(60), 100);
+    }
+
+    #[test]
+    fn merges_repeats_and_drops_frames_too_short_to_show() {
+        let path = std::env::temp_dir().join(format!("chip8-{}-merge.gif", std::process::id()));
+        let [a, b, c] =
+            [Color::RED, Color::GREEN, Color::BLUE].map(|color| Image::new(4, 2, color));
+        let mut gif = GifWriter::create(&path);
+        for _ in 0..6 {
+            gif.frame(&a).unwrap();
+        }
+        // b lasts a single frame and is replaced by c
+        gif.frame(&b).unwrap();
+        for _ in 0..6 {
+            gif.frame(&c).unwrap();
+        }
+        gif.finish().unwrap();
+
+        let delays = delays(&path);
+        std::fs::remove_file(&path).unwrap();
+        // a for frames 0..6, c from frame 6 to the end at 13
+        assert_eq!(delays, [10, 11]);
+    }
+
+    #[test]
+    fn frames_of_another_size_are_stretched_to_the_first() {
+        let path = std::env::temp_dir().join(format!("chip8-{}-stretch.gif", std::process::id()));
+        let mut gif = GifWriter::create(&path);
+        for _ in 0..6 {
+            gif.frame(&Image::new(4, 2, Color::RED)).unwrap();
+        }
+        for _ in 0..6 {
+            gif.frame(&Image::new(8, 4, Color::BLUE)).unwrap();
+        }
+        gif.finish().unwrap();
+
+        let file = File::open(&path).unwrap();
+        let mut decoder = gif::DecodeOptions::new().read_info(file).unwrap();
+        let mut sizes = Vec::new();
+        while let Some(frame) = decoder.read_next_frame().unwrap() {
+            sizes.push((frame.width, frame.height));
+        }
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(sizes, [(4, 2), (4, 2)]);
+    }
+}
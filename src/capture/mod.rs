@@ -0,0 +1,48 @@
+mod gif;
+mod recorder;
+mod screenshot;
+mod wav;
+
+pub use recorder::Recorder;
+pub use screenshot::{load_png, save_png};
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// `<prefix>-YYYYMMDD-HHMMSS-mmm.<extension>` in the working directory,
+// UTC so names sort in the order they were taken
+pub fn timestamped(prefix: &str, extension: &str) -> PathBuf {
+    PathBuf::from(format!("{}-{}.{}", prefix, timestamp(), extension))
+}
+
+// `YYYYMMDD-HHMMSS-mmm`, UTC
+pub fn timestamp() -> String {
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
+    let seconds = now.as_secs();
+    let (year, month, day) = civil_date((seconds / 86400) as i64);
+    let time = seconds % 86400;
+    format!(
+        "{:04}{:02}{:02}-{:02}{:02}{:02}-{:03}",
+        year,
+        month,
+        day,
+        time / 3600,
+        time / 60 % 60,
+        time % 60,
+        now.subsec_millis()
+    )
+}
+
+// days since 1970-01-01 to (year, month, day), from Howard Hinnant's
+// date algorithms
+fn civil_date(days: i64) -> (i64, u32, u32) {
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
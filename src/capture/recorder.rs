@@ -0,0 +1,148 @@
+use super::gif::GifWriter;
+use super::wav::WavWriter;
+use crate::audio::Audio;
+use crate::chip::Cpu;
+use crate::options::Options;
+use crate::video::Video;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+const SAMPLE_RATE: u32 = 44100;
+
+// Raw RGB24 frames, one after the other, for muxing with ffmpeg
+struct RawWriter {
+    path: PathBuf,
+    file: BufWriter<File>,
+    size: (usize, usize),
+    frames: u64,
+}
+
+// Records what the cpu shows and plays, one call per emulated frame.
+// A failing output is reported and closed, the emulation goes on.
+pub struct Recorder {
+    native: bool, // one pixel per chip 8 pixel
+    gif: Option<GifWriter>,
+    raw: Option<RawWriter>,
+    wav: Option<Audio<WavWriter>>,
+}
+
+impl Recorder {
+    pub fn new(native: bool) -> Self {
+        Self {
+            native,
+            gif: None,
+            raw: None,
+            wav: None,
+        }
+    }
+
+    pub fn start_gif(&mut self, path: &Path) {
+        self.gif = Some(GifWriter::create(path));
+    }
+
+    pub fn start_raw(&mut self, path: &Path) -> Result<(), String> {
+        let file = File::create(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        self.raw = Some(RawWriter {
+            path: path.to_path_buf(),
+            file: BufWriter::new(file),
+            size: (0, 0),
+            frames: 0,
+        });
+        Ok(())
+    }
+
+    pub fn start_wav(&mut self, path: &Path, options: &Options) -> Result<(), String> {
+        let wav = WavWriter::create(path, SAMPLE_RATE)?;
+        self.wav = Some(Audio::new(wav, options));
+        Ok(())
+    }
+
+    // the recorded sound of the last program stops with it
+    pub fn reset_audio(&mut self) {
+        if let Some(wav) = &mut self.wav {
+            wav.reset();
+        }
+    }
+
+    pub fn recording_gif(&self) -> bool {
+        self.gif.is_some()
+    }
+
+    pub fn stop_gif(&mut self) {
+        if let Some(gif) = self.gif.take() {
+            let path = gif.path().to_path_buf();
+            report(gif.finish().map(|_| format!("saved {}", path.display())));
+        }
+    }
+
+    pub fn frame(&mut self, video: &Video, cpu: &Cpu) {
+        if self.gif.is_some() || self.raw.is_some() {
+            let image = video.capture(&cpu.display, self.native);
+            if let Some(gif) = &mut self.gif {
+                if let Err(e) = gif.frame(&image) {
+                    eprintln!("{}", e);
+                    self.gif = None;
+                }
+            }
+            if let Some(raw) = &mut self.raw {
+                if raw.frames == 0 {
+                    raw.size = (image.width, image.height);
+                }
+                // the stream has one geometry, that of its first frame
+                let rgb = if (image.width, image.height) == raw.size {
+                    image.to_rgb()
+                } else {
+                    image.resized(raw.size.0, raw.size.1).to_rgb()
+                };
+                if let Err(e) = raw.file.write_all(&rgb) {
+                    eprintln!("{}: {}", raw.path.display(), e);
+                    self.raw = None;
+                } else {
+                    raw.frames += 1;
+                }
+            }
+        }
+
+        if let Some(wav) = &mut self.wav {
+            if let Err(e) = wav.frame(&cpu.sound_events()) {
+                eprintln!("{}", e);
+                self.wav = None;
+            }
+        }
+    }
+
+    // close every output, printing what was saved
+    pub fn finish(&mut self) {
+        self.stop_gif();
+        if let Some(mut raw) = self.raw.take() {
+            let (width, height) = raw.size;
+            report(
+                raw.file
+                    .flush()
+                    .map(|_| {
+                        format!(
+                            "saved {}: {} frames, rawvideo rgb24 {}x{} at 60 fps",
+                            raw.path.display(),
+                            raw.frames,
+                            width,
+                            height
+                        )
+                    })
+                    .map_err(|e| format!("{}: {}", raw.path.display(), e)),
+            );
+        }
+        if let Some(wav) = self.wav.take() {
+            let wav = wav.into_sink();
+            let path = wav.path().to_path_buf();
+            report(wav.finish().map(|_| format!("saved {}", path.display())));
+        }
+    }
+}
+
+fn report(result: Result<String, String>) {
+    match result {
+        Ok(message) => eprintln!("{}", message),
+        Err(e) => eprintln!("{}", e),
+    }
+}
@@ -0,0 +1,71 @@
+use crate::image::Image;
+use sdl2::pixels::Color;
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::Path;
+
+// 8 bit RGB, no alpha
+pub fn save_png(image: &Image, path: &Path) -> Result<(), String> {
+    let error = |e: &dyn std::fmt::Display| format!("{}: {}", path.display(), e);
+    let file = File::create(path).map_err(|e| error(&e))?;
+    let mut encoder = png::Encoder::new(
+        BufWriter::new(file),
+        image.width as u32,
+        image.height as u32,
+    );
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder.write_header().map_err(|e| error(&e))?;
+    writer
+        .write_image_data(&image.to_rgb())
+        .map_err(|e| error(&e))
+}
+
+// any PNG the png crate reads, as 8 bit RGB
+pub fn load_png(path: &Path) -> Result<Image, String> {
+    let error = |e: &dyn std::fmt::Display| format!("{}: {}", path.display(), e);
+    let file = File::open(path).map_err(|e| error(&e))?;
+    let mut decoder = png::Decoder::new(file);
+    decoder.set_transformations(png::Transformations::EXPAND | png::Transformations::STRIP_16);
+    let mut reader = decoder.read_info().map_err(|e| error(&e))?;
+    let mut buffer = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut buffer).map_err(|e| error(&e))?;
+    let (width, height) = (info.width as usize, info.height as usize);
+    let mut image = Image::new(width, height, Color::BLACK);
+    let channels = info.color_type.samples();
+    for (i, pixel) in buffer.chunks(channels).take(width * height).enumerate() {
+        let color = match channels {
+            1 | 2 => Color::RGB(pixel[0], pixel[0], pixel[0]), // grey, with alpha
+            _ => Color::RGB(pixel[0], pixel[1], pixel[2]),
+        };
+        image.set(i % width, i / width, color);
+    }
+    Ok(image)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn png_reads_back_the_pixels_saved() {
+        let mut image = Image::new(3, 2, Color::RGB(10, 20, 30));
+        image.set(2, 1, Color::RGB(200, 100, 50));
+        let path = std::env::temp_dir().join(format!("chip8-{}-round.png", std::process::id()));
+        save_png(&image, &path).unwrap();
+        let loaded = load_png(&path);
+        std::fs::remove_file(&path).unwrap();
+        assert!(loaded.unwrap() == image);
+    }
+
+    #[test]
+    fn reports_the_path_of_a_failed_save() {
+        let path = Path::new("/nonexistent/dir/shot.png");
+        let error = save_png(&Image::new(1, 1, Color::BLACK), path).unwrap_err();
+        assert!(
+            error.starts_with("/nonexistent/dir/shot.png: "),
+            "{}",
+            error
+        );
+    }
+}
@@ -0,0 +1,107 @@
+use crate::audio::SampleSink;
+use std::fs::File;
+use std::io::{BufWriter, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+// 16 bit mono PCM. The sizes in the header are filled in by `finish`.
+pub struct WavWriter {
+    path: PathBuf,
+    file: BufWriter<File>,
+    sample_rate: u32,
+    samples: u32,
+}
+
+impl WavWriter {
+    pub fn create(path: &Path, sample_rate: u32) -> Result<Self, String> {
+        let error = |e: std::io::Error| format!("{}: {}", path.display(), e);
+        let mut file = BufWriter::new(File::create(path).map_err(error)?);
+
+        let mut header = Vec::with_capacity(44);
+        header.extend_from_slice(b"RIFF");
+        header.extend_from_slice(&0u32.to_le_bytes()); // riff size
+        header.extend_from_slice(b"WAVEfmt ");
+        header.extend_from_slice(&16u32.to_le_bytes());
+        header.extend_from_slice(&1u16.to_le_bytes()); // pcm
+        header.extend_from_slice(&1u16.to_le_bytes()); // mono
+        header.extend_from_slice(&sample_rate.to_le_bytes());
+        header.extend_from_slice(&(sample_rate * 2).to_le_bytes()); // bytes per second
+        header.extend_from_slice(&2u16.to_le_bytes()); // bytes per sample
+        header.extend_from_slice(&16u16.to_le_bytes()); // bits per sample
+        header.extend_from_slice(b"data");
+        header.extend_from_slice(&0u32.to_le_bytes()); // data size
+        file.write_all(&header).map_err(error)?;
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            file,
+            sample_rate,
+            samples: 0,
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn finish(mut self) -> Result<(), String> {
+        let data = self.samples * 2;
+        let result = (|| {
+            self.file.seek(SeekFrom::Start(4))?;
+            self.file.write_all(&(36 + data).to_le_bytes())?;
+            self.file.seek(SeekFrom::Start(40))?;
+            self.file.write_all(&data.to_le_bytes())?;
+            self.file.flush()
+        })();
+        result.map_err(|e| self.error(&e))
+    }
+
+    fn error(&self, error: &dyn std::fmt::Display) -> String {
+        format!("{}: {}", self.path.display(), error)
+    }
+}
+
+impl SampleSink for WavWriter {
+    fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    fn write(&mut self, samples: &[f32]) -> Result<(), String> {
+        for sample in samples {
+            let value = (sample.clamp(-1.0, 1.0) * i16::MAX as f32) as i16;
+            self.file
+                .write_all(&value.to_le_bytes())
+                .map_err(|e| self.error(&e))?;
+        }
+        self.samples += samples.len() as u32;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn writes_clamped_16_bit_samples_and_fills_in_the_sizes() {
+        let path = std::env::temp_dir().join(format!("chip8-{}-beep.wav", std::process::id()));
+        let mut wav = WavWriter::create(&path, 8000).unwrap();
+        wav.write(&[0.0, 1.0]).unwrap();
+        wav.write(&[-1.0, 2.0]).unwrap();
+        wav.finish().unwrap();
+        let bytes = std::fs::read(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let word = |at: usize| {
+            u32::from_le_bytes([bytes[at], bytes[at + 1], bytes[at + 2], bytes[at + 3]])
+        };
+        assert_eq!(&bytes[0..4], b"RIFF");
+        assert_eq!(word(4), 36 + 8);
+        assert_eq!(word(24), 8000);
+        assert_eq!(word(40), 8);
+        let samples: Vec<i16> = bytes[44..]
+            .chunks(2)
+            .map(|pair| i16::from_le_bytes([pair[0], pair[1]]))
+            .collect();
+        assert_eq!(samples, vec![0, 32767, -32767, 32767]);
+    }
+}
@@ -0,0 +1,68 @@
+// Cowgod style mnemonic for one instruction, `DW` for anything that
+// does not decode. The assembler accepts the same syntax back.
+pub fn disassemble(opcode: u16) -> String {
+    let x = (opcode & 0x0F00) >> 8;
+    let y = (opcode & 0x00F0) >> 4;
+    let n = opcode & 0x000F;
+    let kk = opcode & 0x00FF;
+    let nnn = opcode & 0x0FFF;
+
+    match opcode & 0xF000 {
+        0x0000 => match opcode {
+            0x00E0 => "CLS".to_string(),
+            0x00EE => "RET".to_string(),
+            _ => format!("SYS {:#05X}", nnn),
+        },
+        0x1000 => format!("JP {:#05X}", nnn),
+        0x2000 => format!("CALL {:#05X}", nnn),
+        0x3000 => format!("SE V{:X}, {:#04X}", x, kk),
+        0x4000 => format!("SNE V{:X}, {:#04X}", x, kk),
+        0x5000 if n == 0 => format!("SE V{:X}, V{:X}", x, y),
+        0x6000 => format!("LD V{:X}, {:#04X}", x, kk),
+        0x7000 => format!("ADD V{:X}, {:#04X}", x, kk),
+        0x8000 => {
+            let mnemonic = match n {
+                0x0 => "LD",
+                0x1 => "OR",
+                0x2 => "AND",
+                0x3 => "XOR",
+                0x4 => "ADD",
+                0x5 => "SUB",
+                0x6 => "SHR",
+                0x7 => "SUBN",
+                0xE => "SHL",
+                _ => return data(opcode),
+            };
+            format!("{} V{:X}, V{:X}", mnemonic, x, y)
+        }
+        0x9000 if n == 0 => format!("SNE V{:X}, V{:X}", x, y),
+        0xA000 => format!("LD I, {:#05X}", nnn),
+        0xB000 => format!("JP V0, {:#05X}", nnn),
+        0xC000 => format!("RND V{:X}, {:#04X}", x, kk),
+        0xD000 => format!("DRW V{:X}, V{:X}, {:#03X}", x, y, n),
+        0xE000 => match kk {
+            0x9E => format!("SKP V{:X}", x),
+            0xA1 => format!("SKNP V{:X}", x),
+            _ => data(opcode),
+        },
+        0xF000 => match kk {
+            0x02 if x == 0 => "LD AUDIO, [I]".to_string(),
+            0x07 => format!("LD V{:X}, DT", x),
+            0x0A => format!("LD V{:X}, K", x),
+            0x15 => format!("LD DT, V{:X}", x),
+            0x18 => format!("LD ST, V{:X}", x),
+            0x1E => format!("ADD I, V{:X}", x),
+            0x29 => format!("LD F, V{:X}", x),
+            0x3A => format!("LD PITCH, V{:X}", x),
+            0x33 => format!("LD B, V{:X}", x),
+            0x55 => format!("LD [I], V{:X}", x),
+            0x65 => format!("LD V{:X}, [I]", x),
+            _ => data(opcode),
+        },
+        _ => data(opcode),
+    }
+}
+
+fn data(opcode: u16) -> String {
+    format!("DW {:#06X}", opcode)
+}
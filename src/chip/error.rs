@@ -0,0 +1,56 @@
+use std::fmt;
+use std::io;
+
+// Errors raised while executing an instruction
+#[derive(Debug, Clone, PartialEq)]
+pub enum ExecError {
+    InvalidInstruction(u16),
+    StackOverflow(usize), // carries the configured stack depth
+    StackUnderflow,
+    ProtectedWrite(usize), // write into the interpreter area
+}
+
+impl fmt::Display for ExecError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ExecError::InvalidInstruction(opcode) => {
+                write!(f, "invalid instruction {:04X}", opcode)
+            }
+            ExecError::StackOverflow(depth) => write!(f, "stack overflow (depth {})", depth),
+            ExecError::StackUnderflow => write!(f, "stack underflow (RET with empty stack)"),
+            ExecError::ProtectedWrite(address) => {
+                write!(f, "write to protected interpreter area at {:#05X}", address)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ExecError {}
+
+// Errors raised while loading a rom into memory
+#[derive(Debug)]
+pub enum LoadError {
+    Io(io::Error),
+    RomTooLarge { size: usize, max: usize },
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LoadError::Io(e) => write!(f, "{}", e),
+            LoadError::RomTooLarge { size, max } => write!(
+                f,
+                "rom is {} bytes but only {} bytes are available for programs",
+                size, max
+            ),
+        }
+    }
+}
+
+impl std::error::Error for LoadError {}
+
+impl From<io::Error> for LoadError {
+    fn from(e: io::Error) -> Self {
+        LoadError::Io(e)
+    }
+}
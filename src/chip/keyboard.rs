@@ -4,21 +4,29 @@ pub struct KeyBoard {
     pub pressed_key: HashMap<u8, bool>,
     pub last_pressed_key: Option<u8>,
     pub should_wait_for_key: bool,
+    pub polled: u16, // keys checked by Ex9E and ExA1 during the last frame, one bit each
 }
 
 impl KeyBoard {
     pub fn new() -> Self {
-        let mut pressed_key: HashMap<u8, bool> = HashMap::with_capacity(15);
-        for i in 0..15 {
+        let mut pressed_key: HashMap<u8, bool> = HashMap::with_capacity(16);
+        for i in 0..16 {
             pressed_key.insert(i, false);
         }
         Self {
             pressed_key,
             last_pressed_key: None,
             should_wait_for_key: false,
+            polled: 0,
         }
     }
 
+    // whether a key is down, as the rom asks it
+    pub fn poll(&mut self, key: u8) -> bool {
+        self.polled |= 1 << key;
+        self.pressed_key[&key]
+    }
+
     pub fn on_key_down(&mut self, key: u8) {
         self.pressed_key.insert(key, true);
         self.last_pressed_key = Some(key);
@@ -35,3 +43,17 @@ impl KeyBoard {
         self.pressed_key.insert(key, false);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn polling_remembers_which_keys_were_asked_for() {
+        let mut keyboard = KeyBoard::new();
+        keyboard.on_key_down(0x3);
+        assert!(keyboard.poll(0x3));
+        assert!(!keyboard.poll(0xF));
+        assert_eq!(keyboard.polled, 1 << 0x3 | 1 << 0xF);
+    }
+}
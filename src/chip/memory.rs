@@ -0,0 +1,171 @@
+pub const RAM_SIZE: usize = 4096;
+pub const PROGRAM_START: usize = 0x200; // everything below belongs to the interpreter
+pub const ETI660_PROGRAM_START: usize = 0x600; // ETI 660 roms start here
+pub const FONT_SIZE: usize = 80; // 16 sprites of 5 bytes
+
+// On the COSMAC VIP the top of ram is used by the interpreter:
+// 0xEA0..0xEFF stack, work area and variables, 0xF00..0xFFF display buffer
+pub const VIP_RESERVED_START: usize = 0xEA0;
+pub const VIP_DISPLAY_ADDRESS: usize = 0xF00;
+
+// Layout of the 4KB address space
+#[derive(Clone)]
+pub struct MemoryMap {
+    pub load_address: usize,        // where the rom is copied
+    pub entry_point: Option<usize>, // initial pc, defaults to the load address
+    pub font_address: usize,
+    pub interpreter: Option<Vec<u8>>, // image copied to 0x000 before the font
+    pub vip_layout: bool,             // reserve the VIP regions at the top of ram
+    pub protect_interpreter: bool,    // writes below the load address are execution errors
+}
+
+impl MemoryMap {
+    pub fn entry_point(&self) -> usize {
+        self.entry_point.unwrap_or(self.load_address)
+    }
+
+    // first address a rom can not occupy
+    pub fn program_end(&self) -> usize {
+        if self.vip_layout {
+            VIP_RESERVED_START
+        } else {
+            RAM_SIZE
+        }
+    }
+
+    // font and interpreter image must both fit below the program area
+    pub fn validate(&self) -> Result<(), String> {
+        if self.load_address >= self.program_end() {
+            return Err(format!(
+                "load address {:#05X} leaves no room for a program",
+                self.load_address
+            ));
+        }
+        if self.entry_point() >= self.program_end() - 1 {
+            return Err(format!(
+                "entry point {:#05X} is outside the program area",
+                self.entry_point()
+            ));
+        }
+        if self.font_address + FONT_SIZE > self.load_address {
+            return Err(format!(
+                "font at {:#05X} does not fit in the interpreter area",
+                self.font_address
+            ));
+        }
+        if let Some(image) = &self.interpreter {
+            if image.len() > self.load_address {
+                return Err(format!(
+                    "interpreter image is {} bytes, at most {} fit below {:#05X}",
+                    image.len(),
+                    self.load_address,
+                    self.load_address
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    pub fn is_protected(&self, address: usize) -> bool {
+        self.protect_interpreter && address < self.load_address
+    }
+}
+
+impl Default for MemoryMap {
+    fn default() -> Self {
+        Self {
+            load_address: PROGRAM_START,
+            entry_point: None,
+            font_address: 0x050,
+            interpreter: None,
+            vip_layout: false,
+            protect_interpreter: false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn font_must_end_below_the_program() {
+        let mut map = MemoryMap::default();
+        assert_eq!(map.validate(), Ok(()));
+        map.font_address = PROGRAM_START - FONT_SIZE;
+        assert_eq!(map.validate(), Ok(()));
+        map.font_address += 1;
+        assert_eq!(
+            map.validate(),
+            Err("font at 0x1B1 does not fit in the interpreter area".to_string())
+        );
+    }
+
+    #[test]
+    fn interpreter_image_must_fit_below_the_program() {
+        let mut map = MemoryMap {
+            interpreter: Some(vec![0; PROGRAM_START]),
+            ..MemoryMap::default()
+        };
+        assert_eq!(map.validate(), Ok(()));
+        map.interpreter = Some(vec![0; PROGRAM_START + 1]);
+        assert_eq!(
+            map.validate(),
+            Err("interpreter image is 513 bytes, at most 512 fit below 0x200".to_string())
+        );
+    }
+
+    #[test]
+    fn vip_layout_keeps_roms_out_of_the_reserved_top() {
+        let mut map = MemoryMap::default();
+        assert_eq!(map.program_end(), RAM_SIZE);
+        map.vip_layout = true;
+        assert_eq!(map.program_end(), 0xEA0);
+    }
+
+    #[test]
+    fn protects_only_the_interpreter_area_when_asked() {
+        let mut map = MemoryMap::default();
+        assert!(!map.is_protected(0x000));
+        map.protect_interpreter = true;
+        assert!(map.is_protected(0x000));
+        assert!(map.is_protected(0x1FF));
+        assert!(!map.is_protected(0x200));
+    }
+
+    #[test]
+    fn entry_point_follows_the_load_address() {
+        let mut map = MemoryMap {
+            load_address: ETI660_PROGRAM_START,
+            ..MemoryMap::default()
+        };
+        assert_eq!(map.entry_point(), 0x600);
+        map.entry_point = Some(0x700);
+        assert_eq!(map.entry_point(), 0x700);
+        // the interpreter area grows with the load address
+        assert!(!map.is_protected(0x5FF));
+        map.protect_interpreter = true;
+        assert!(map.is_protected(0x5FF));
+    }
+
+    #[test]
+    fn load_address_and_entry_point_must_leave_a_program() {
+        let mut map = MemoryMap {
+            load_address: 0xEA0,
+            vip_layout: true,
+            ..MemoryMap::default()
+        };
+        assert_eq!(
+            map.validate(),
+            Err("load address 0xEA0 leaves no room for a program".to_string())
+        );
+        map.load_address = 0x200;
+        map.entry_point = Some(0xE9F);
+        assert_eq!(
+            map.validate(),
+            Err("entry point 0xE9F is outside the program area".to_string())
+        );
+        map.entry_point = Some(0xE9E);
+        assert_eq!(map.validate(), Ok(()));
+    }
+}
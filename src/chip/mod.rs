@@ -1,161 +1,271 @@
+mod disasm;
 mod display;
+mod error;
 mod keyboard;
+mod memory;
+mod quirks;
+mod rom;
 mod sound;
-
-use display::Display;
-use keyboard::KeyBoard;
-use rand::Rng;
-use sdl2::event::Event;
-use sdl2::keyboard::Keycode;
-use sound::Sound;
-use std::fs;
-use std::io::Read;
-use std::thread;
-use std::time::{Duration, Instant};
+mod stack;
+mod trace;
+
+use crate::options::Options;
+pub use disasm::disassemble;
+pub use display::Display;
+pub use error::{ExecError, LoadError};
+pub use keyboard::KeyBoard;
+pub use memory::MemoryMap;
+pub use memory::{ETI660_PROGRAM_START, PROGRAM_START};
+use memory::{FONT_SIZE, RAM_SIZE, VIP_DISPLAY_ADDRESS};
+pub use quirks::{Quirks, PLATFORMS};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+pub use rom::Rom;
+use sound::SoundEvents;
+pub use sound::{SoundChange, SoundEvent};
+pub use stack::VIP_STACK_SLOTS;
+use stack::{Stack, VIP_STACK_ADDRESS};
+use std::collections::BTreeMap;
+use std::ops::Range;
+pub use trace::{State, Trace, TraceFilter};
+
+// looks at an instruction and the state before it runs, false stops
+type Check<'a> = &'a mut dyn FnMut(u16, &State) -> bool;
 
 pub struct Cpu {
-    display: Display,
-    keyboard: KeyBoard,
-    sound: Sound,
-    event_pump: sdl2::EventPump,
-    stack: Vec<u16>,
-    ram: [u8; 4096],     // 4KB memory
+    pub display: Display,
+    pub keyboard: KeyBoard,
+    pub trace: Option<Trace>, // executed instructions, when asked for
+    memory_map: MemoryMap,
+    quirks: Quirks,
+    tickrate: u32, // instructions per frame
+    rng: StdRng,
+    rom_sha1: Option<String>,
+    stack: Stack,
+    ram: [u8; RAM_SIZE], // 4KB memory
     registers: [u8; 16], // 16 8-bit register
     index: u16,          // 1 16-bit register (store memory address)
     delay_timer: u8,
     sound_timer: u8,
     pc: u16,      // Program counter
     paused: bool, // Is cpu paused
+    sound_events: SoundEvents,
+    frame: u64,                        // frames run so far
+    batch_position: u32,               // instruction within the current frame
+    drew: bool,                        // the screen was cleared or drawn to during the last frame
+    cycles: u64,                       // instructions executed so far
+    written: Vec<Option<u64>>,         // frame of the last instruction write to each byte
+    sprite_height: u8,                 // rows of the last sprite drawn
+    sprite_sources: BTreeMap<u16, u8>, // every address drawn from, with its most rows
 }
 
 impl Cpu {
-    pub fn new() -> Self {
-        let sdl_context = sdl2::init().unwrap();
-        let event_pump = sdl_context.event_pump().unwrap();
-        let display = Display::new(&sdl_context, "Chip 8", 16, 64, 32);
+    pub fn new(options: &Options) -> Self {
+        let display = Display::new();
         let keyboard = KeyBoard::new();
-        let sound = Sound::new(&sdl_context);
-        let mut ram: [u8; 4096] = [0; 4096];
+        let mut ram: [u8; RAM_SIZE] = [0; RAM_SIZE];
+        let memory_map = options.memory_map.clone();
+        let entry_point = memory_map.entry_point() as u16;
+
+        // interpreter image first so the font can be placed over it
+        if let Some(image) = &memory_map.interpreter {
+            ram[..image.len()].copy_from_slice(image);
+        }
 
         //laod sprites into memory
-        load_sprites(&mut ram);
+        load_sprites(&mut ram[memory_map.font_address..]);
+
+        let stack = if options.stack_in_ram {
+            Stack::in_ram(options.stack_depth, VIP_STACK_ADDRESS)
+        } else {
+            Stack::new(options.stack_depth)
+        };
+
+        let rng = match options.seed {
+            Some(seed) => StdRng::seed_from_u64(seed),
+            None => StdRng::from_entropy(),
+        };
 
         Self {
             display,
             keyboard,
-            sound,
-            event_pump,
-            stack: vec![0],
+            trace: None,
+            memory_map,
+            quirks: options.quirks,
+            tickrate: options.tickrate,
+            rng,
+            rom_sha1: None,
+            stack,
             ram,
             registers: [0; 16],
             index: 0,
             sound_timer: 0,
             delay_timer: 0,
-            pc: 0x200, // Program start at 0x200 on chip 8
+            pc: entry_point, // 0x200 on chip 8, 0x600 on the ETI 660
             paused: false,
+            sound_events: SoundEvents::new(),
+            frame: 0,
+            batch_position: 0,
+            drew: false,
+            cycles: 0,
+            written: vec![None; RAM_SIZE],
+            sprite_height: 0,
+            sprite_sources: BTreeMap::new(),
         }
     }
 
     // load program into memory
-    pub fn load(&mut self, rom: &mut fs::File) {
-        let mut temp = vec![0_u8];
-        let count = rom.read_to_end(&mut temp).expect("Load failed");
-        for i in 0..count {
-            self.ram[0x200 + i] = temp[i + 1];
+    pub fn load(&mut self, rom: &Rom) -> Result<(), LoadError> {
+        let program = &rom.data;
+        let start = self.memory_map.load_address;
+        let max = self.memory_map.program_end() - start;
+        if program.len() > max {
+            return Err(LoadError::RomTooLarge {
+                size: program.len(),
+                max,
+            });
         }
+        self.ram[start..start + program.len()].copy_from_slice(program);
+        self.rom_sha1 = Some(rom.sha1.clone());
+        Ok(())
     }
 
-    // This will start execution of the program
-    pub fn execute(&mut self) {
-        'main: loop {
-            let t0 = Instant::now();
-            if let Some(e) = self.event_pump.poll_event() {
-                match e {
-                    Event::KeyDown {
-                        keycode: Some(key), ..
-                    } => {
-                        match key {
-                            Keycode::A => self.keyboard.on_key_down(0xA),
-                            Keycode::B => self.keyboard.on_key_down(0xB),
-                            Keycode::C => self.keyboard.on_key_down(0xC),
-                            Keycode::D => self.keyboard.on_key_down(0xD),
-                            Keycode::E => self.keyboard.on_key_down(0xE),
-                            Keycode::F => self.keyboard.on_key_down(0xF),
-                            Keycode::Num1 => self.keyboard.on_key_down(1),
-                            Keycode::Num2 => self.keyboard.on_key_down(2),
-                            Keycode::Num3 => self.keyboard.on_key_down(3),
-                            Keycode::Num4 | Keycode::Left => self.keyboard.on_key_down(4),
-                            Keycode::Num5 | Keycode::Space => self.keyboard.on_key_down(5),
-                            Keycode::Num6 | Keycode::Right => self.keyboard.on_key_down(6),
-                            Keycode::Num7 => self.keyboard.on_key_down(7),
-                            Keycode::Num8 => self.keyboard.on_key_down(8),
-                            Keycode::Num9 => self.keyboard.on_key_down(9),
-                            Keycode::Num0 => self.keyboard.on_key_down(0),
-                            Keycode::Q => {
-                                break 'main;
-                            } //break 'main loop
-                            _ => {} //ignore other keyboard char
-                        }
-                    }
+    // Start over from the entry point with ram left as it is, like the
+    // reset switch of a machine whose program stays in memory
+    pub fn soft_reset(&mut self) {
+        let was_on = self.sound_timer != 0;
+        self.display.clear();
+        self.keyboard = KeyBoard::new();
+        self.stack.clear();
+        self.registers = [0; 16];
+        self.index = 0;
+        self.delay_timer = 0;
+        self.sound_timer = 0;
+        self.pc = self.memory_map.entry_point() as u16;
+        self.paused = false;
+        self.sound_changed(was_on, 0.0);
+    }
 
-                    Event::KeyUp {
-                        keycode: Some(key), ..
-                    } => match key {
-                        Keycode::A => self.keyboard.on_key_up(0xA),
-                        Keycode::B => self.keyboard.on_key_up(0xB),
-                        Keycode::C => self.keyboard.on_key_up(0xC),
-                        Keycode::D => self.keyboard.on_key_up(0xD),
-                        Keycode::E => self.keyboard.on_key_up(0xE),
-                        Keycode::F => self.keyboard.on_key_up(0xF),
-                        Keycode::Num1 => self.keyboard.on_key_up(1),
-                        Keycode::Num2 => self.keyboard.on_key_up(2),
-                        Keycode::Num3 => self.keyboard.on_key_up(3),
-                        Keycode::Num4 | Keycode::Left => self.keyboard.on_key_up(4),
-                        Keycode::Num5 | Keycode::Space => self.keyboard.on_key_up(5),
-                        Keycode::Num6 | Keycode::Right => self.keyboard.on_key_up(6),
-                        Keycode::Num7 => self.keyboard.on_key_up(7),
-                        Keycode::Num8 => self.keyboard.on_key_up(8),
-                        Keycode::Num9 => self.keyboard.on_key_up(9),
-                        Keycode::Num0 => self.keyboard.on_key_up(0),
-
-                        _ => {}
-                    },
-                    _ => {} // ignore mouse and other event
-                }
-            } // events matching end here
+    // Run one 60hz frame: tickrate instructions, then the timers
+    pub fn run_frame(&mut self) -> Result<(), ExecError> {
+        self.frame(None).map(|_| ())
+    }
 
-            // execute 8 instruction (500hz/60fps) = 8 instruction per frame
-            self.execute_one_batch(16);
+    // run_frame showing each instruction's opcode and the state before
+    // it runs to `check` first. A false answer stops before that
+    // instruction, with false returned.
+    pub fn run_frame_checked(
+        &mut self,
+        check: &mut dyn FnMut(u16, &State) -> bool,
+    ) -> Result<bool, ExecError> {
+        self.frame(Some(check))
+    }
 
-            // update display after each  batch
-            self.display.render();
+    fn frame(&mut self, check: Option<Check>) -> Result<bool, ExecError> {
+        self.drew = false;
+        self.keyboard.polled = 0;
 
-            // update sound time
-            if self.sound_timer == 0 {
-                self.sound.pause();
-            } else {
-                self.sound.resume();
-                self.sound_timer -= 1;
-            }
+        // execute tickrate instructions per frame (16 by default, ~960hz at 60fps)
+        if !self.execute_one_batch(self.tickrate, check)? {
+            return Ok(false);
+        }
 
-            // update delay time
-            if self.delay_timer != 0 {
-                self.delay_timer -= 1;
-            }
+        // update sound time, the tone stops at the end of the frame
+        if self.sound_timer != 0 {
+            self.sound_timer -= 1;
+            self.sound_changed(true, 1.0);
+        }
+
+        // update delay time
+        if self.delay_timer != 0 {
+            self.delay_timer -= 1;
+        }
+        self.frame += 1;
+        Ok(true)
+    }
+
+    // beeper changes during the last frame
+    pub fn sound_events(&self) -> Vec<SoundEvent> {
+        self.sound_events.frame(self.frame.wrapping_sub(1))
+    }
+
+    pub fn drew(&self) -> bool {
+        self.drew
+    }
+
+    pub fn pc(&self) -> u16 {
+        self.pc
+    }
+
+    pub fn ram(&self) -> &[u8] {
+        &self.ram
+    }
+
+    pub fn index(&self) -> u16 {
+        self.index
+    }
+
+    // return addresses on the stack, oldest first
+    pub fn stack(&self) -> Vec<u16> {
+        self.stack.entries(&self.ram)
+    }
+
+    // change a byte from outside the program, like a debugger does
+    pub fn poke(&mut self, address: usize, value: u8) {
+        let address = address % RAM_SIZE;
+        self.ram[address] = value;
+        self.written[address] = Some(self.frame);
+    }
+
+    pub fn frames_since_write(&self, address: usize) -> Option<u64> {
+        self.written[address % RAM_SIZE].map(|frame| self.frame - frame)
+    }
 
-            //sleep for remaining microsecond of this iteration
-            let elapsed_time = t0.elapsed().as_micros();
+    pub fn font_range(&self) -> Range<usize> {
+        self.memory_map.font_address..self.memory_map.font_address + FONT_SIZE
+    }
+
+    // bytes the last sprite drawn took, at least one
+    pub fn sprite_height(&self) -> u8 {
+        self.sprite_height.max(1)
+    }
+
+    pub fn state(&self) -> State {
+        State {
+            pc: self.pc,
+            registers: self.registers,
+            index: self.index,
+            delay_timer: self.delay_timer,
+            sound_timer: self.sound_timer,
+        }
+    }
+
+    pub fn sprite_sources(&self) -> &BTreeMap<u16, u8> {
+        &self.sprite_sources
+    }
+
+    pub fn cycles(&self) -> u64 {
+        self.cycles
+    }
 
-            // roughly 16666 micros in 1/60 seconds
-            thread::sleep(Duration::from_micros((16666 - elapsed_time) as u64));
-        } // main loop ends here
+    // print machine state after an execution error
+    pub fn report(&self, error: &ExecError) {
+        eprintln!("error at {:#05X}: {}", self.pc, error);
+        if let Some(sha1) = &self.rom_sha1 {
+            eprintln!("rom sha1: {}", sha1);
+        }
+        eprint!("stack:");
+        for address in self.stack.entries(&self.ram) {
+            eprint!(" {:#05X}", address);
+        }
+        eprintln!();
     }
 }
 
 // helper function
 #[inline]
 fn load_sprites(memory: &mut [u8]) {
-    let sprites: [u8; 80] = [
+    let sprites: [u8; FONT_SIZE] = [
         0xF0, 0x90, 0x90, 0x90, 0xF0, // 0
         0x20, 0x60, 0x20, 0x20, 0x70, // 1
         0xF0, 0x10, 0xF0, 0x80, 0xF0, // 2
@@ -180,7 +290,7 @@ fn load_sprites(memory: &mut [u8]) {
 }
 
 impl Cpu {
-    fn execute_instruction(&mut self, opcode: u16) {
+    fn execute_instruction(&mut self, opcode: u16) -> Result<(), ExecError> {
         self.pc += 2;
         let x = (opcode & 0x0F00) >> 8;
         let x = x as usize;
@@ -193,12 +303,16 @@ impl Cpu {
         match opcode & 0xF000 {
             0x000 => match opcode {
                 // CLS
-                0x00E0 => self.display.clear(),
+                0x00E0 => {
+                    self.display.clear();
+                    self.drew = true;
+                    self.sync_display_memory();
+                }
 
                 //RET
-                0x00EE => self.pc = self.stack.pop().unwrap(),
+                0x00EE => self.pc = self.stack.pop(&self.ram)?,
 
-                _ => panic!("Invalid instruction {} ", opcode),
+                _ => return Err(ExecError::InvalidInstruction(opcode)),
             },
 
             //1nnn JP addr
@@ -206,7 +320,10 @@ impl Cpu {
 
             //2nnn CALL addr
             0x2000 => {
-                self.stack.push(self.pc);
+                if let Some(position) = self.stack.push(self.pc, RAM_SIZE)? {
+                    self.write_ram(position, (self.pc >> 8) as u8)?;
+                    self.write_ram(position + 1, self.pc as u8)?;
+                }
                 self.pc = nnn;
             }
             //3xkk SE vx,byte
@@ -243,13 +360,22 @@ impl Cpu {
                     0 => self.registers[x] = self.registers[y],
 
                     // 8xy1 OR vx,vy
-                    1 => self.registers[x] = self.registers[x] | self.registers[y],
+                    1 => {
+                        self.registers[x] |= self.registers[y];
+                        self.logic_quirk();
+                    }
 
                     // 8xy2 AND vx,vy
-                    2 => self.registers[x] = self.registers[x] & self.registers[y],
+                    2 => {
+                        self.registers[x] &= self.registers[y];
+                        self.logic_quirk();
+                    }
 
                     // 8xy3 XOR vx,vy
-                    3 => self.registers[x] = self.registers[x] ^ self.registers[y],
+                    3 => {
+                        self.registers[x] ^= self.registers[y];
+                        self.logic_quirk();
+                    }
 
                     // 8xy4 ADD vx,vy
                     4 => {
@@ -267,11 +393,12 @@ impl Cpu {
                     //8xy5 SUB vx,vy
                     5 => {
                         if self.registers[x] > self.registers[y] {
-                            self.registers[x] = self.registers[x] - self.registers[y];
+                            self.registers[x] -= self.registers[y];
                             // set Vf flag
                             self.registers[0xF] = 1;
                         } else {
-                            self.registers[x] = self.registers[x] - self.registers[y];
+                            // borrow, wraps around
+                            self.registers[x] = self.registers[x].wrapping_sub(self.registers[y]);
                             // clear Vf flag
                             self.registers[0xF] = 0;
                         }
@@ -279,13 +406,10 @@ impl Cpu {
 
                     //8xy6 SHR vx {,vy}
                     6 => {
-                        // least significatn bit is 1
-                        if self.registers[x] & 1 == 1 {
-                            self.registers[0xF] = 1
-                        } else {
-                            self.registers[0xF] = 0
-                        }
-                        self.registers[x] /= 2;
+                        let value = self.shift_source(x, y);
+                        self.registers[x] = value >> 1;
+                        // least significatn bit goes to vf
+                        self.registers[0xF] = value & 1;
                     }
 
                     //8xy7 SUBN vx,vy
@@ -295,7 +419,8 @@ impl Cpu {
                             // set Vf flag
                             self.registers[0xF] = 1;
                         } else {
-                            self.registers[x] = self.registers[y] - self.registers[x];
+                            // borrow, wraps around
+                            self.registers[x] = self.registers[y].wrapping_sub(self.registers[x]);
                             // clear Vf flag
                             self.registers[0xF] = 0;
                         }
@@ -303,16 +428,12 @@ impl Cpu {
 
                     //8xyE SHL vx {, vy}
                     0xE => {
-                        // most significatn bit is 1
-                        if self.registers[x] & 1 << 7 == 1 {
-                            self.registers[0xF] = 1
-                        } else {
-                            self.registers[0xF] = 0
-                        }
-                        let sum = self.registers[x] as u16 * 216;
-                        self.registers[x] = sum as u8;
+                        let value = self.shift_source(x, y);
+                        self.registers[x] = value << 1;
+                        // most significatn bit goes to vf
+                        self.registers[0xF] = value >> 7;
                     }
-                    _ => panic!("Invalid Instruction {}", opcode),
+                    _ => return Err(ExecError::InvalidInstruction(opcode)),
                 }
             }
 
@@ -326,12 +447,19 @@ impl Cpu {
             //Annn
             0xA000 => self.index = opcode & 0x0FFF,
 
-            //Bnnn JP v0,addr
-            0xB000 => self.pc = self.registers[0] as u16 + nnn,
+            //Bnnn JP v0,addr (Bxnn JP vx,addr with the jump quirk)
+            0xB000 => {
+                let offset = if self.quirks.jump {
+                    self.registers[x]
+                } else {
+                    self.registers[0]
+                };
+                self.pc = offset as u16 + nnn;
+            }
 
             //cxkk RND vx,byte
             0xC000 => {
-                let random_byte = rand::thread_rng().gen::<u8>();
+                let random_byte = self.rng.gen::<u8>();
                 self.registers[x] = kk & random_byte;
             }
 
@@ -339,18 +467,28 @@ impl Cpu {
                 //Dxyn
                 self.registers[0xf] = 0;
                 let n = (opcode & 0x000F) as usize;
+                self.sprite_height = n as u8;
+                if n > 0 {
+                    let rows = self.sprite_sources.entry(self.index).or_insert(0);
+                    *rows = (*rows).max(n as u8);
+                }
+                // the starting position always wraps, the sprite itself only with the wrap quirk
+                let x0 = self.registers[x] as usize % 64;
+                let y0 = self.registers[y] as usize % 32;
                 for row in 0..n {
+                    if !self.quirks.wrap && y0 + row >= 32 {
+                        break;
+                    }
                     let mut sprite = self.ram[(self.index + row as u16) as usize];
                     // get a row 10010011
                     for col in 0..8 {
+                        if !self.quirks.wrap && x0 + col >= 64 {
+                            break;
+                        }
                         // msb of the row is set the plot pixel
                         if sprite & 0b1000_0000u8 > 0 {
                             // draw given pixel at
-                            if self.display.set_pixel(
-                                self.registers[x] as usize + col as usize,
-                                self.registers[y] as usize + row as usize,
-                            ) == 1
-                            {
+                            if self.display.set_pixel(x0 + col, y0 + row) == 1 {
                                 self.registers[0xf] = 1
                             }
                         }
@@ -358,18 +496,20 @@ impl Cpu {
                         sprite <<= 1;
                     }
                 }
+                self.drew = true;
+                self.sync_display_memory();
             }
 
             0xE000 => match opcode & 0x00FF {
                 //Ex9E SKP v /rlater
                 0x9E => {
-                    if self.keyboard.pressed_key[&(self.registers[x])] {
+                    if self.keyboard.poll(self.registers[x] & 0xF) {
                         self.pc += 2;
                     }
                 }
                 //ExA1 SKNP vx
                 0xA1 => {
-                    if !(self.keyboard.pressed_key[&(self.registers[x])]) {
+                    if !(self.keyboard.poll(self.registers[x] & 0xF)) {
                         self.pc += 2;
                     }
                 }
@@ -378,6 +518,14 @@ impl Cpu {
 
             0xF000 => {
                 match opcode & 0x00FF {
+                    //F002 LD AUDIO,[I] (xo-chip)
+                    0x02 if x == 0 => {
+                        let mut pattern = [0; 16];
+                        for (i, byte) in pattern.iter_mut().enumerate() {
+                            *byte = self.ram[(self.index as usize + i) % RAM_SIZE];
+                        }
+                        self.sound_event(SoundChange::Pattern(pattern), self.batch_offset());
+                    }
                     //Fx07 LD vx,DT
                     0x07 => self.registers[x] = self.delay_timer,
                     //Fx0A LD vx,K /rlater
@@ -388,28 +536,40 @@ impl Cpu {
                     //Fx15 LD DT,vx
                     0x15 => self.delay_timer = self.registers[x],
                     //Fx18 LD ST,vx
-                    0x18 => self.sound_timer = self.registers[x],
+                    0x18 => {
+                        let was_on = self.sound_timer != 0;
+                        self.sound_timer = self.registers[x];
+                        self.sound_changed(was_on, self.batch_offset());
+                    }
+                    //Fx3A LD PITCH,vx (xo-chip)
+                    0x3A => {
+                        let pitch = SoundChange::Pitch(self.registers[x]);
+                        self.sound_event(pitch, self.batch_offset());
+                    }
                     //Fx1E ADD I,vx
                     0x1E => self.index += self.registers[x] as u16,
                     //Fx29 LD F,vx
                     0x29 =>
                     // load starting index of font for value(vx)
                     {
-                        self.index = self.registers[x] as u16 * 5
+                        self.index =
+                            (self.memory_map.font_address + self.registers[x] as usize * 5) as u16
                     }
 
                     //Fx33 LD B,vx
                     0x33 => {
-                        self.ram[self.index as usize] = (x / 100) as u8;
-                        self.ram[(self.index + 1) as usize] = ((x / 10) % 10) as u8;
-                        self.ram[(self.index + 2) as usize] = (x % 10) as u8;
+                        let value = self.registers[x];
+                        self.write_ram(self.index as usize, value / 100)?;
+                        self.write_ram((self.index + 1) as usize, (value / 10) % 10)?;
+                        self.write_ram((self.index + 2) as usize, value % 10)?;
                     }
 
                     //Fx55 LD [I], Vx
                     0x55 => {
                         for i in 0..=x {
-                            self.ram[(self.index + i as u16) as usize] = self.registers[i];
+                            self.write_ram((self.index + i as u16) as usize, self.registers[i])?;
                         }
+                        self.memory_quirk(x);
                     }
 
                     //Fx65 LD vx,[I]
@@ -417,19 +577,119 @@ impl Cpu {
                         for i in 0..=x {
                             self.registers[i] = self.ram[(self.index + i as u16) as usize];
                         }
+                        self.memory_quirk(x);
                     }
-                    _ => panic!("Invalid Instruction {}", opcode),
+                    _ => return Err(ExecError::InvalidInstruction(opcode)),
                 }
             }
 
-            _ => panic!("Invalid Instruction {}", opcode),
+            _ => return Err(ExecError::InvalidInstruction(opcode)),
         }
+        Ok(())
     }
 }
 
 impl Cpu {
-    fn execute_one_batch(&mut self, batch_size: u8) {
-        for _ in 0..batch_size {
+    // 8xy6/8xyE operate on vy unless the shift quirk is on
+    fn shift_source(&self, x: usize, y: usize) -> u8 {
+        if self.quirks.shift {
+            self.registers[x]
+        } else {
+            self.registers[y]
+        }
+    }
+
+    fn logic_quirk(&mut self) {
+        if self.quirks.logic {
+            self.registers[0xF] = 0;
+        }
+    }
+
+    // how Fx55/Fx65 leave the index register
+    fn memory_quirk(&mut self, x: usize) {
+        if self.quirks.memory_leave_i_unchanged {
+            return;
+        }
+        if self.quirks.memory_increment_by_x {
+            self.index += x as u16;
+        } else {
+            self.index += x as u16 + 1;
+        }
+    }
+
+    // all instruction writes to ram go through here
+    fn write_ram(&mut self, address: usize, value: u8) -> Result<(), ExecError> {
+        let address = address % RAM_SIZE;
+        if self.memory_map.is_protected(address) {
+            return Err(ExecError::ProtectedWrite(address));
+        }
+        self.ram[address] = value;
+        self.written[address] = Some(self.frame);
+        Ok(())
+    }
+
+    // mirror the framebuffer into the VIP's display region (1 bit per pixel)
+    fn sync_display_memory(&mut self) {
+        if !self.memory_map.vip_layout {
+            return;
+        }
+        for y in 0..32 {
+            for byte in 0..8 {
+                let mut bits = 0u8;
+                for bit in 0..8 {
+                    bits <<= 1;
+                    if self.display.is_set(byte * 8 + bit, y) {
+                        bits |= 1;
+                    }
+                }
+                self.ram[VIP_DISPLAY_ADDRESS + y * 8 + byte] = bits;
+            }
+        }
+    }
+
+    // record a start or stop of the beeper at a position in the frame
+    fn sound_changed(&mut self, was_on: bool, position: f32) {
+        let on = self.sound_timer != 0;
+        if on != was_on {
+            let change = if on {
+                SoundChange::On
+            } else {
+                SoundChange::Off
+            };
+            self.sound_event(change, position);
+        }
+    }
+
+    // how far into the frame the current instruction runs
+    fn batch_offset(&self) -> f32 {
+        self.batch_position as f32 / self.tickrate as f32
+    }
+
+    fn sound_event(&mut self, change: SoundChange, position: f32) {
+        self.sound_events.push(SoundEvent {
+            frame: self.frame,
+            position,
+            change,
+        });
+    }
+
+    // a trace that can no longer be written is dropped, the rom goes on
+    fn write_trace(&mut self, write: impl FnOnce(&mut Trace) -> std::io::Result<()>) {
+        if let Some(trace) = &mut self.trace {
+            if let Err(e) = write(trace) {
+                eprintln!("trace: {}", e);
+                self.trace = None;
+            }
+        }
+    }
+
+    fn execute_one_batch(
+        &mut self,
+        batch_size: u32,
+        mut check: Option<Check>,
+    ) -> Result<bool, ExecError> {
+        for position in 0..batch_size {
+            self.batch_position = position;
             let mut instruction = self.ram[self.pc as usize] as u16;
             instruction <<= 8;
             instruction |= self.ram[(self.pc + 1) as usize] as u16;
@@ -443,8 +703,246 @@ impl Cpu {
                 }
             } else {
                 // if cpu is not paused then execute instruction
-                self.execute_instruction(instruction);
+                if let Some(check) = &mut check {
+                    if !check(instruction, &self.state()) {
+                        return Ok(false);
+                    }
+                }
+                let before = self.trace.as_ref().map(|_| self.state());
+                if let Err(e) = self.execute_instruction(instruction) {
+                    // leave pc pointing at the faulting instruction
+                    self.pc -= 2;
+                    let (cycle, pc) = (self.cycles + 1, self.pc);
+                    self.write_trace(|trace| trace.error(cycle, pc, instruction, &e));
+                    return Err(e);
+                }
+                self.cycles += 1;
+                if let Some(before) = before {
+                    let (cycle, after) = (self.cycles, self.state());
+                    self.write_trace(|trace| trace.record(cycle, instruction, &before, &after));
+                }
+                // with the vblank quirk a draw ends the frame
+                if self.quirks.vblank && instruction & 0xF000 == 0xD000 {
+                    break;
+                }
             }
         }
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cpu_with(quirks: Quirks, program: &[u16]) -> Cpu {
+        let options = Options {
+            quirks,
+            ..Options::default()
+        };
+        let mut cpu = Cpu::new(&options);
+        let data = program.iter().flat_map(|op| op.to_be_bytes().to_vec());
+        let rom = Rom {
+            data: data.collect(),
+            sha1: String::new(),
+        };
+        cpu.load(&rom).unwrap();
+        cpu
+    }
+
+    // run the whole program
+    fn run(quirks: Quirks, program: &[u16]) -> Cpu {
+        let mut cpu = cpu_with(quirks, program);
+        cpu.execute_one_batch(program.len() as u32, None).unwrap();
+        cpu
+    }
+
+    fn quirks(set: impl Fn(&mut Quirks)) -> Quirks {
+        let mut quirks = Quirks::default();
+        set(&mut quirks);
+        quirks
+    }
+
+    #[test]
+    fn shift_quirk_picks_the_source_register() {
+        // V1 = 0x81, V2 = 0x02, SHR V1, V2
+        let program = [0x6181, 0x6202, 0x8126];
+        let cpu = run(quirks(|q| q.shift = true), &program);
+        assert_eq!((cpu.registers[1], cpu.registers[0xF]), (0x40, 1));
+        let cpu = run(quirks(|q| q.shift = false), &program);
+        assert_eq!((cpu.registers[1], cpu.registers[0xF]), (0x01, 0));
+    }
+
+    #[test]
+    fn shl_doubles_and_keeps_the_top_bit() {
+        let cpu = run(Quirks::default(), &[0x6181, 0x812E]);
+        assert_eq!((cpu.registers[1], cpu.registers[0xF]), (0x02, 1));
+        let cpu = run(Quirks::default(), &[0x6141, 0x812E]);
+        assert_eq!((cpu.registers[1], cpu.registers[0xF]), (0x82, 0));
+    }
+
+    #[test]
+    fn sub_and_subn_wrap_on_borrow() {
+        let cpu = run(Quirks::default(), &[0x6105, 0x6207, 0x8125]);
+        assert_eq!((cpu.registers[1], cpu.registers[0xF]), (0xFE, 0));
+        let cpu = run(Quirks::default(), &[0x6107, 0x6205, 0x8127]);
+        assert_eq!((cpu.registers[1], cpu.registers[0xF]), (0xFE, 0));
+    }
+
+    #[test]
+    fn skp_and_sknp_know_every_key() {
+        let mut cpu = cpu_with(Quirks::default(), &[0x610F, 0xE19E, 0x0000, 0x61FF, 0xE1A1]);
+        cpu.keyboard.on_key_down(0xF);
+        cpu.execute_one_batch(2, None).unwrap();
+        assert_eq!(cpu.pc, 0x206);
+        // only the low nibble of vx names the key
+        cpu.execute_one_batch(2, None).unwrap();
+        assert_eq!(cpu.pc, 0x20A);
+    }
+
+    #[test]
+    fn jump_quirk_adds_vx_instead_of_v0() {
+        let program = [0x6010, 0x6220, 0xB210];
+        assert_eq!(run(quirks(|q| q.jump = false), &program).pc, 0x220);
+        assert_eq!(run(quirks(|q| q.jump = true), &program).pc, 0x230);
+    }
+
+    #[test]
+    fn logic_quirk_resets_vf() {
+        let program = [0x6F01, 0x8121];
+        assert_eq!(run(quirks(|q| q.logic = false), &program).registers[0xF], 1);
+        assert_eq!(run(quirks(|q| q.logic = true), &program).registers[0xF], 0);
+    }
+
+    #[test]
+    fn memory_quirks_decide_where_i_ends() {
+        let program = [0xA300, 0xF255];
+        let index = |set: fn(&mut Quirks)| run(quirks(set), &program).index;
+        assert_eq!(index(|q| q.memory_leave_i_unchanged = true), 0x300);
+        assert_eq!(
+            index(|q| {
+                q.memory_leave_i_unchanged = false;
+                q.memory_increment_by_x = true;
+            }),
+            0x302
+        );
+        assert_eq!(
+            index(|q| {
+                q.memory_leave_i_unchanged = false;
+                q.memory_increment_by_x = false;
+            }),
+            0x303
+        );
+    }
+
+    #[test]
+    fn wrap_quirk_decides_if_sprites_clip() {
+        // digit 0 at x = 62: its top row is four pixels wide
+        let program = [0x613E, 0x6200, 0x6300, 0xF329, 0xD125];
+        let cpu = run(quirks(|q| q.wrap = true), &program);
+        assert!(cpu.display.is_set(63, 0) && cpu.display.is_set(0, 0));
+        let cpu = run(quirks(|q| q.wrap = false), &program);
+        assert!(cpu.display.is_set(63, 0) && !cpu.display.is_set(0, 0));
+    }
+
+    #[test]
+    fn vblank_quirk_ends_the_frame_after_a_draw() {
+        let program = [0xD001, 0x6101];
+        let mut cpu = cpu_with(quirks(|q| q.vblank = true), &program);
+        cpu.run_frame().unwrap();
+        assert_eq!((cpu.pc, cpu.registers[1]), (0x202, 0));
+        let mut cpu = cpu_with(quirks(|q| q.vblank = false), &program);
+        cpu.execute_one_batch(2, None).unwrap();
+        assert_eq!((cpu.pc, cpu.registers[1]), (0x204, 1));
+    }
+
+    #[test]
+    fn fx18_stamps_the_beep_with_its_place_in_the_frame() {
+        // 16 instructions a frame, Fx18 is the fourth
+        let program = [0x6002, 0x6100, 0x6100, 0xF018, 0x1208];
+        let mut cpu = cpu_with(Quirks::default(), &program);
+        cpu.run_frame().unwrap();
+        let on = SoundEvent {
+            frame: 0,
+            position: 3.0 / 16.0,
+            change: SoundChange::On,
+        };
+        assert_eq!(cpu.sound_events(), vec![on]);
+        // the timer runs out at the end of the second frame
+        cpu.run_frame().unwrap();
+        let off = SoundEvent {
+            frame: 1,
+            position: 1.0,
+            change: SoundChange::Off,
+        };
+        assert_eq!(cpu.sound_events(), vec![off]);
+    }
+
+    #[test]
+    fn polled_keys_are_collected_per_frame() {
+        // the rom checks key A once, then loops
+        let mut cpu = cpu_with(Quirks::default(), &[0x610A, 0xE19E, 0x1204]);
+        cpu.run_frame().unwrap();
+        assert_eq!(cpu.keyboard.polled, 1 << 0xA);
+        cpu.run_frame().unwrap();
+        assert_eq!(cpu.keyboard.polled, 0);
+    }
+
+    #[test]
+    fn soft_reset_keeps_ram_and_clears_the_rest() {
+        // V0 = 5, I = 0x300, [I] = V0, sound on, CALL 0x20C, loop
+        let program = [0x6005, 0xA300, 0xF055, 0xF018, 0x220C, 0x0000, 0x120C];
+        let mut cpu = cpu_with(Quirks::default(), &program);
+        cpu.execute_one_batch(6, None).unwrap();
+        assert_eq!((cpu.pc, cpu.stack.entries(&cpu.ram).len()), (0x20C, 1));
+        cpu.soft_reset();
+
+        assert_eq!(cpu.pc, 0x200);
+        assert_eq!((cpu.registers, cpu.index, cpu.sound_timer), ([0; 16], 0, 0));
+        assert!(cpu.stack.entries(&cpu.ram).is_empty());
+        assert_eq!(cpu.ram[0x300], 5);
+        // the beep stops with the reset
+        let changes: Vec<SoundChange> = cpu
+            .sound_events
+            .frame(cpu.frame)
+            .iter()
+            .map(|event| event.change)
+            .collect();
+        assert_eq!(changes, [SoundChange::On, SoundChange::Off]);
+    }
+
+    #[test]
+    fn sprite_sources_keep_the_most_rows_drawn() {
+        // I = 0x300, draw 3 rows then 5, I = 0x310, draw 2, draw 0 rows
+        let program = [0xA300, 0xD013, 0xD015, 0xA310, 0xD012, 0xA320, 0xD010];
+        let cpu = run(quirks(|q| q.vblank = false), &program);
+        let sources: Vec<(u16, u8)> = cpu.sprite_sources().iter().map(|(a, r)| (*a, *r)).collect();
+        assert_eq!(sources, vec![(0x300, 5), (0x310, 2)]);
+    }
+
+    #[test]
+    fn bcd_stores_the_digits_of_vx() {
+        // V3 = 234, I = 0x300, LD B, V3
+        let cpu = run(Quirks::default(), &[0x63EA, 0xA300, 0xF333]);
+        assert_eq!(cpu.ram()[0x300..0x303], [2, 3, 4]);
+    }
+
+    #[test]
+    fn calls_write_a_stack_in_ram_like_any_other_write() {
+        let options = Options {
+            stack_in_ram: true,
+            ..Options::default()
+        };
+        let mut cpu = Cpu::new(&options);
+        // CALL 0x204, then loop there
+        let rom = Rom {
+            data: vec![0x22, 0x04, 0x00, 0x00, 0x12, 0x04],
+            sha1: String::new(),
+        };
+        cpu.load(&rom).unwrap();
+        cpu.execute_one_batch(1, None).unwrap();
+        assert_eq!(cpu.stack(), vec![0x202]);
+        assert_eq!(cpu.ram()[0xEA0..0xEA2], [0x02, 0x02]);
+        assert_eq!(cpu.frames_since_write(0xEA1), Some(0));
     }
 }
@@ -0,0 +1,158 @@
+// Behaviour that differs between chip 8 interpreters. Names follow the
+// community CHIP-8 database (https://github.com/chip-8/chip-8-database).
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Quirks {
+    pub shift: bool,                    // 8xy6/8xyE shift vx in place and ignore vy
+    pub memory_increment_by_x: bool,    // Fx55/Fx65 leave I at I + x
+    pub memory_leave_i_unchanged: bool, // Fx55/Fx65 do not touch I
+    pub wrap: bool,                     // sprites wrap around the screen edges instead of clipping
+    pub jump: bool,                     // Bxnn jumps to xnn + vx instead of nnn + v0
+    pub vblank: bool,                   // Dxyn waits for the next frame
+    pub logic: bool,                    // 8xy1/8xy2/8xy3 reset vf
+}
+
+// platform ids as used in the database
+pub const PLATFORMS: [&str; 5] = [
+    "originalChip8",
+    "modernChip8",
+    "chip48",
+    "superchip",
+    "xochip",
+];
+
+impl Quirks {
+    pub fn for_platform(platform: &str) -> Option<Self> {
+        let quirks = match platform {
+            "originalChip8" | "hybridVIP" => Self {
+                shift: false,
+                memory_increment_by_x: false,
+                memory_leave_i_unchanged: false,
+                wrap: false,
+                jump: false,
+                vblank: true,
+                logic: true,
+            },
+            "modernChip8" => Self {
+                shift: false,
+                memory_increment_by_x: false,
+                memory_leave_i_unchanged: false,
+                wrap: false,
+                jump: false,
+                vblank: false,
+                logic: false,
+            },
+            "chip48" => Self {
+                shift: true,
+                memory_increment_by_x: true,
+                memory_leave_i_unchanged: false,
+                wrap: false,
+                jump: true,
+                vblank: false,
+                logic: false,
+            },
+            "superchip" | "superchip1" => Self {
+                shift: true,
+                memory_increment_by_x: false,
+                memory_leave_i_unchanged: true,
+                wrap: false,
+                jump: true,
+                vblank: false,
+                logic: false,
+            },
+            "xochip" => Self {
+                shift: false,
+                memory_increment_by_x: false,
+                memory_leave_i_unchanged: false,
+                wrap: true,
+                jump: false,
+                vblank: false,
+                logic: false,
+            },
+            _ => return None,
+        };
+        Some(quirks)
+    }
+
+    // set a single quirk by its database name
+    pub fn set(&mut self, name: &str, value: bool) -> bool {
+        match name {
+            "shift" => self.shift = value,
+            "memoryIncrementByX" => self.memory_increment_by_x = value,
+            "memoryLeaveIUnchanged" => self.memory_leave_i_unchanged = value,
+            "wrap" => self.wrap = value,
+            "jump" => self.jump = value,
+            "vblank" => self.vblank = value,
+            "logic" => self.logic = value,
+            _ => return false,
+        }
+        true
+    }
+}
+
+// what this emulator always did before quirks were configurable
+impl Default for Quirks {
+    fn default() -> Self {
+        Self {
+            shift: true,
+            memory_increment_by_x: false,
+            memory_leave_i_unchanged: true,
+            wrap: true,
+            jump: false,
+            vblank: false,
+            logic: false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_listed_platform_has_a_preset() {
+        for platform in PLATFORMS.iter() {
+            assert!(Quirks::for_platform(platform).is_some(), "{}", platform);
+        }
+        assert_eq!(Quirks::for_platform("pdp8"), None);
+    }
+
+    #[test]
+    fn database_aliases_share_a_preset() {
+        assert_eq!(
+            Quirks::for_platform("hybridVIP"),
+            Quirks::for_platform("originalChip8")
+        );
+        assert_eq!(
+            Quirks::for_platform("superchip1"),
+            Quirks::for_platform("superchip")
+        );
+    }
+
+    #[test]
+    fn original_chip8_differs_from_the_default() {
+        let vip = Quirks::for_platform("originalChip8").unwrap();
+        assert!(!vip.shift && vip.vblank && vip.logic && !vip.memory_leave_i_unchanged);
+        let default = Quirks::default();
+        assert!(default.shift && default.memory_leave_i_unchanged);
+        assert!(!default.vblank && !default.logic && !default.jump);
+    }
+
+    #[test]
+    fn set_uses_the_database_names() {
+        let mut quirks = Quirks::default();
+        assert!(quirks.set("memoryIncrementByX", true));
+        assert!(quirks.set("wrap", false));
+        assert!(quirks.memory_increment_by_x && !quirks.wrap);
+        assert!(!quirks.set("memory_increment_by_x", false));
+        assert!(quirks.memory_increment_by_x);
+    }
+
+    #[test]
+    fn only_xochip_wraps_sprites() {
+        for platform in PLATFORMS.iter() {
+            let wrap = Quirks::for_platform(platform).unwrap().wrap;
+            assert_eq!(wrap, *platform == "xochip", "{}", platform);
+        }
+        assert!(Quirks::default().wrap);
+    }
+}
@@ -0,0 +1,31 @@
+use super::error::LoadError;
+use sha1::{Digest, Sha1};
+use std::fs;
+use std::io::Read;
+
+// Program image together with its SHA-1, used to identify it in the rom database
+pub struct Rom {
+    pub data: Vec<u8>,
+    pub sha1: String,
+}
+
+impl Rom {
+    pub fn read(file: &mut fs::File) -> Result<Self, LoadError> {
+        let mut data = Vec::new();
+        file.read_to_end(&mut data)?;
+        let sha1 = format!("{:x}", Sha1::digest(&data));
+        Ok(Self { data, sha1 })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hashes_the_rom_as_read() {
+        let rom = Rom::read(&mut fs::File::open("roms/IBM").unwrap()).unwrap();
+        assert_eq!(rom.data.len(), 132);
+        assert_eq!(rom.sha1, "1ba58656810b67fd131eb9af3e3987863bf26c90");
+    }
+}
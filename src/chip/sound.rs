@@ -1,53 +1,114 @@
-use sdl2::audio::{AudioCallback, AudioDevice, AudioSpecDesired};
-use sdl2::Sdl;
+use std::collections::VecDeque;
 
-struct SquareWave {
-    phase_inc: f32,
-    phase: f32,
-    volume: f32,
+// far more changes than a frame can make
+const CAPACITY: usize = 256;
+
+// What the program did to the sound
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SoundChange {
+    On,
+    Off,
+    Pattern([u8; 16]), // xo-chip F002, 128 one bit samples
+    Pitch(u8),         // xo-chip Fx3A
+}
+
+// A sound change stamped with the emulated frame and the position
+// within it: 0.0 at the start, 1.0 at the end
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SoundEvent {
+    pub frame: u64,
+    pub position: f32,
+    pub change: SoundChange,
+}
+
+// Ring buffer of recent events, the oldest are dropped when it is full
+pub struct SoundEvents {
+    events: VecDeque<SoundEvent>,
 }
 
-impl AudioCallback for SquareWave {
-    type Channel = f32;
-    fn callback(&mut self, out: &mut [Self::Channel]) {
-        for x in out.iter_mut() {
-            *x = if self.phase <= 0.5 {
-                self.volume
-            } else {
-                -self.volume
-            };
-            self.phase = (self.phase + self.phase_inc) % 1.0;
+impl SoundEvents {
+    pub fn new() -> Self {
+        Self {
+            events: VecDeque::with_capacity(CAPACITY),
         }
     }
-}
 
-pub struct Sound {
-    device: AudioDevice<SquareWave>,
+    pub fn push(&mut self, event: SoundEvent) {
+        if self.events.len() == CAPACITY {
+            self.events.pop_front();
+        }
+        self.events.push_back(event);
+    }
+
+    // events of one frame, in the order they happened
+    pub fn frame(&self, frame: u64) -> Vec<SoundEvent> {
+        self.events
+            .iter()
+            .filter(|event| event.frame == frame)
+            .copied()
+            .collect()
+    }
 }
 
-impl Sound {
-    pub fn new(sdl_context: &Sdl) -> Self {
-        let audio_subsystem = sdl_context.audio().unwrap();
-        let spec_desired = AudioSpecDesired {
-            freq: Some(44100),
-            channels: Some(1),
-            samples: None,
-        };
-        let device = audio_subsystem
-            .open_playback(None, &spec_desired, |spec| SquareWave {
-                phase_inc: 440.0 / spec.freq as f32,
-                phase: 0.0,
-                volume: 0.25,
-            })
-            .unwrap();
-        Self { device }
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chip::{Cpu, Rom};
+    use crate::options::Options;
+
+    fn event(frame: u64) -> SoundEvent {
+        SoundEvent {
+            frame,
+            position: 0.0,
+            change: SoundChange::On,
+        }
+    }
+
+    #[test]
+    fn events_are_read_by_frame() {
+        let mut events = SoundEvents::new();
+        events.push(event(1));
+        events.push(event(2));
+        events.push(event(2));
+        assert_eq!(events.frame(2), vec![event(2), event(2)]);
+        assert!(events.frame(3).is_empty());
     }
 
-    pub fn pause(&self) {
-        self.device.pause()
+    #[test]
+    fn a_full_ring_drops_the_oldest() {
+        let mut events = SoundEvents::new();
+        for frame in 0..=CAPACITY as u64 {
+            events.push(event(frame));
+        }
+        assert!(events.frame(0).is_empty());
+        assert_eq!(events.frame(CAPACITY as u64), vec![event(CAPACITY as u64)]);
     }
 
-    pub fn resume(&self) {
-        self.device.resume()
+    #[test]
+    fn f002_and_fx3a_send_the_pattern_and_pitch() {
+        let mut data = vec![
+            0xA2, 0x0A, // LD I, 0x20A
+            0xF0, 0x02, // LD AUDIO, [I]
+            0x60, 0x70, // LD V0, 0x70
+            0xF0, 0x3A, // LD PITCH, V0
+            0x12, 0x08, // JP 0x208
+        ];
+        let pattern: Vec<u8> = (0..16).collect();
+        data.extend(&pattern);
+        let mut cpu = Cpu::new(&Options::default());
+        cpu.load(&Rom {
+            data,
+            sha1: String::new(),
+        })
+        .unwrap();
+        cpu.run_frame().unwrap();
+
+        let mut buffer = [0; 16];
+        buffer.copy_from_slice(&pattern);
+        let changes: Vec<SoundChange> = cpu.sound_events().iter().map(|e| e.change).collect();
+        assert_eq!(
+            changes,
+            vec![SoundChange::Pattern(buffer), SoundChange::Pitch(0x70)]
+        );
     }
 }
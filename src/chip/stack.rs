@@ -0,0 +1,176 @@
+use super::error::ExecError;
+
+// The COSMAC VIP interpreter keeps its stack in ram at 0xEA0..0xECF
+pub const VIP_STACK_ADDRESS: usize = 0xEA0;
+// return addresses that fit there, two bytes each
+pub const VIP_STACK_SLOTS: usize = (0xED0 - VIP_STACK_ADDRESS) / 2;
+
+// Return-address stack with a fixed depth like the real machines
+// (12 entries on the VIP, 16 on SCHIP). When `base` is set the entries
+// live in emulated ram, two bytes each (big endian), so roms that peek
+// or poke the stack see the same values the interpreter uses.
+pub struct Stack {
+    slots: Vec<u16>,
+    sp: usize,
+    depth: usize,
+    base: Option<usize>,
+}
+
+impl Stack {
+    pub fn new(depth: usize) -> Self {
+        Self {
+            slots: vec![0; depth],
+            sp: 0,
+            depth,
+            base: None,
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.sp = 0;
+    }
+
+    // stack stored in emulated ram starting at `base`
+    pub fn in_ram(depth: usize, base: usize) -> Self {
+        Self {
+            slots: Vec::new(),
+            sp: 0,
+            depth,
+            base: Some(base),
+        }
+    }
+
+    // Push `address`. A stack in ram does not write the entry itself but
+    // returns where its two bytes go, so the cpu stores them like any
+    // other write to ram.
+    pub fn push(&mut self, address: u16, ram_size: usize) -> Result<Option<usize>, ExecError> {
+        if self.sp == self.depth {
+            return Err(ExecError::StackOverflow(self.depth));
+        }
+        let position = match self.base {
+            Some(base) => {
+                let position = base + self.sp * 2;
+                // options keep the depth within the reserved region, this
+                // only stops a stack running off the end of ram
+                if position + 1 >= ram_size {
+                    return Err(ExecError::StackOverflow(self.depth));
+                }
+                Some(position)
+            }
+            None => {
+                self.slots[self.sp] = address;
+                None
+            }
+        };
+        self.sp += 1;
+        Ok(position)
+    }
+
+    pub fn pop(&mut self, ram: &[u8]) -> Result<u16, ExecError> {
+        if self.sp == 0 {
+            return Err(ExecError::StackUnderflow);
+        }
+        self.sp -= 1;
+        Ok(self.get(self.sp, ram))
+    }
+
+    // return addresses currently on the stack, oldest first
+    pub fn entries(&self, ram: &[u8]) -> Vec<u16> {
+        (0..self.sp).map(|i| self.get(i, ram)).collect()
+    }
+
+    fn get(&self, slot: usize, ram: &[u8]) -> u16 {
+        match self.base {
+            Some(base) => {
+                let position = base + slot * 2;
+                (ram[position] as u16) << 8 | ram[position + 1] as u16
+            }
+            None => self.slots[slot],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // push the way the cpu does, writing a ram entry where it belongs
+    fn push(stack: &mut Stack, address: u16, ram: &mut [u8]) -> Result<(), ExecError> {
+        if let Some(position) = stack.push(address, ram.len())? {
+            ram[position] = (address >> 8) as u8;
+            ram[position + 1] = address as u8;
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn pops_in_reverse_order() {
+        let mut ram = [0; 4096];
+        let mut stack = Stack::new(12);
+        push(&mut stack, 0x202, &mut ram).unwrap();
+        push(&mut stack, 0x304, &mut ram).unwrap();
+        assert_eq!(stack.entries(&ram), vec![0x202, 0x304]);
+        assert_eq!(stack.pop(&ram), Ok(0x304));
+        assert_eq!(stack.pop(&ram), Ok(0x202));
+    }
+
+    #[test]
+    fn overflows_past_its_depth() {
+        let mut ram = [0; 4096];
+        let mut stack = Stack::new(12);
+        for i in 0..12 {
+            push(&mut stack, 0x200 + i * 2, &mut ram).unwrap();
+        }
+        assert_eq!(
+            push(&mut stack, 0x300, &mut ram),
+            Err(ExecError::StackOverflow(12))
+        );
+    }
+
+    #[test]
+    fn underflows_when_empty() {
+        let mut ram = [0; 4096];
+        let mut stack = Stack::new(16);
+        assert_eq!(stack.pop(&ram), Err(ExecError::StackUnderflow));
+        push(&mut stack, 0x200, &mut ram).unwrap();
+        stack.clear();
+        assert_eq!(stack.pop(&ram), Err(ExecError::StackUnderflow));
+    }
+
+    #[test]
+    fn keeps_big_endian_entries_in_ram() {
+        let mut ram = [0; 4096];
+        let mut stack = Stack::in_ram(12, VIP_STACK_ADDRESS);
+        push(&mut stack, 0x2A4, &mut ram).unwrap();
+        push(&mut stack, 0x31E, &mut ram).unwrap();
+        assert_eq!(ram[0xEA0..0xEA4], [0x02, 0xA4, 0x03, 0x1E]);
+
+        // a rom poking the stack changes where RET goes
+        ram[0xEA3] = 0x20;
+        assert_eq!(stack.pop(&ram), Ok(0x320));
+    }
+
+    #[test]
+    fn vip_region_holds_24_entries() {
+        assert_eq!(VIP_STACK_SLOTS, 24);
+        let mut ram = [0; 4096];
+        let mut stack = Stack::in_ram(VIP_STACK_SLOTS, VIP_STACK_ADDRESS);
+        for _ in 0..VIP_STACK_SLOTS {
+            push(&mut stack, 0xFFF, &mut ram).unwrap();
+        }
+        assert_eq!(ram[0xECF], 0xFF);
+        assert_eq!(ram[0xED0], 0);
+    }
+
+    #[test]
+    fn overflows_instead_of_running_off_ram() {
+        let mut ram = [0; 4096];
+        let mut stack = Stack::in_ram(100, 0xFFC);
+        push(&mut stack, 0x200, &mut ram).unwrap();
+        push(&mut stack, 0x202, &mut ram).unwrap();
+        assert_eq!(
+            push(&mut stack, 0x204, &mut ram),
+            Err(ExecError::StackOverflow(100))
+        );
+    }
+}
@@ -0,0 +1,235 @@
+use super::disasm::disassemble;
+use super::error::ExecError;
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::ops::RangeInclusive;
+
+// What an instruction can change that a trace shows
+#[derive(Clone, PartialEq)]
+pub struct State {
+    pub pc: u16,
+    pub registers: [u8; 16],
+    pub index: u16,
+    pub delay_timer: u8,
+    pub sound_timer: u8,
+}
+
+// Which instructions go in a trace. Classes are first hex digits of
+// the opcode ("D") or mnemonics ("CALL"), nothing means all of them.
+pub struct TraceFilter {
+    pub range: Option<RangeInclusive<u16>>,
+    pub classes: Vec<String>,
+}
+
+impl TraceFilter {
+    fn matches(&self, pc: u16, opcode: u16, text: &str) -> bool {
+        if self
+            .range
+            .as_ref()
+            .is_some_and(|range| !range.contains(&pc))
+        {
+            return false;
+        }
+        let digit = format!("{:X}", opcode >> 12);
+        let mnemonic = text.split(' ').next().unwrap_or("");
+        self.classes.is_empty()
+            || self.classes.iter().any(|class| {
+                class.eq_ignore_ascii_case(&digit) || class.eq_ignore_ascii_case(mnemonic)
+            })
+    }
+}
+
+// One line per executed instruction, state after it ran:
+//
+//      cycle  pc   op    disassembly         I   DT ST  changed registers
+//         42  21A  8344  ADD V3, V4          2A0 00 00  V3 FF>04 VF 00>01
+//
+// With `last` set, lines are kept in a ring and only written out when
+// an instruction fails, followed by the error.
+pub struct Trace {
+    out: Box<dyn Write>,
+    filter: TraceFilter,
+    last: Option<(usize, VecDeque<String>)>,
+}
+
+impl Trace {
+    pub fn new(out: Box<dyn Write>, filter: TraceFilter, last: Option<usize>) -> Self {
+        Self {
+            out,
+            filter,
+            last: last.map(|n| (n, VecDeque::with_capacity(n))),
+        }
+    }
+
+    pub fn create(path: &str, filter: TraceFilter, last: Option<usize>) -> Result<Self, String> {
+        let file = File::create(path).map_err(|e| format!("{}: {}", path, e))?;
+        Ok(Self::new(Box::new(BufWriter::new(file)), filter, last))
+    }
+
+    pub fn record(
+        &mut self,
+        cycle: u64,
+        opcode: u16,
+        before: &State,
+        after: &State,
+    ) -> io::Result<()> {
+        let text = disassemble(opcode);
+        if !self.filter.matches(before.pc, opcode, &text) {
+            return Ok(());
+        }
+        let mut line = format!(
+            "{:>8}  {:03X}  {:04X}  {:<18}  {:03X} {:02X} {:02X}",
+            cycle, before.pc, opcode, text, after.index, after.delay_timer, after.sound_timer
+        );
+        let mut separator = "  ";
+        for (i, (old, new)) in before.registers.iter().zip(&after.registers).enumerate() {
+            if old != new {
+                line += &format!("{}V{:X} {:02X}>{:02X}", separator, i, old, new);
+                separator = " ";
+            }
+        }
+        match &mut self.last {
+            Some((n, lines)) => {
+                if lines.len() == *n {
+                    lines.pop_front();
+                }
+                lines.push_back(line);
+                Ok(())
+            }
+            None => writeln!(self.out, "{}", line),
+        }
+    }
+
+    // the failed instruction, after the ring when there is one
+    pub fn error(&mut self, cycle: u64, pc: u16, opcode: u16, error: &ExecError) -> io::Result<()> {
+        if let Some((_, lines)) = &mut self.last {
+            for line in lines.drain(..) {
+                writeln!(self.out, "{}", line)?;
+            }
+        }
+        writeln!(
+            self.out,
+            "{:>8}  {:03X}  {:04X}  error: {}",
+            cycle, pc, opcode, error
+        )?;
+        self.out.flush()
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.out.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    // a writer the test can still read after the trace took it
+    #[derive(Clone, Default)]
+    struct Shared(Rc<RefCell<Vec<u8>>>);
+
+    impl Write for Shared {
+        fn write(&mut self, bytes: &[u8]) -> io::Result<usize> {
+            self.0.borrow_mut().extend_from_slice(bytes);
+            Ok(bytes.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl Shared {
+        fn text(&self) -> String {
+            String::from_utf8(self.0.borrow().clone()).unwrap()
+        }
+    }
+
+    fn state(pc: u16) -> State {
+        State {
+            pc,
+            registers: [0; 16],
+            index: 0x2A0,
+            delay_timer: 0,
+            sound_timer: 0,
+        }
+    }
+
+    fn trace(filter: TraceFilter, last: Option<usize>) -> (Trace, Shared) {
+        let out = Shared::default();
+        (Trace::new(Box::new(out.clone()), filter, last), out)
+    }
+
+    fn all() -> TraceFilter {
+        TraceFilter {
+            range: None,
+            classes: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn writes_state_after_and_changed_registers() {
+        let (mut trace, out) = trace(all(), None);
+        // ADD V3, V4 with a carry
+        let mut before = state(0x21A);
+        before.registers[3] = 0xFF;
+        before.registers[4] = 0x05;
+        let mut after = before.clone();
+        after.pc = 0x21C;
+        after.registers[3] = 0x04;
+        after.registers[0xF] = 0x01;
+        after.delay_timer = 0x3C;
+        trace.record(42, 0x8344, &before, &after).unwrap();
+        trace.record(43, 0x00E0, &after, &after).unwrap();
+        assert_eq!(
+            out.text(),
+            format!(
+                "{:>8}  21A  8344  {:<18}  2A0 3C 00  V3 FF>04 VF 00>01\n{:>8}  21C  00E0  {:<18}  2A0 3C 00\n",
+                42,
+                disassemble(0x8344),
+                43,
+                disassemble(0x00E0)
+            )
+        );
+    }
+
+    #[test]
+    fn filters_by_address_and_class() {
+        let filter = TraceFilter {
+            range: Some(0x200..=0x20F),
+            classes: vec!["d".to_string(), "cls".to_string()],
+        };
+        assert!(filter.matches(0x200, 0xD015, &disassemble(0xD015)));
+        assert!(filter.matches(0x20F, 0x00E0, &disassemble(0x00E0)));
+        assert!(!filter.matches(0x210, 0xD015, &disassemble(0xD015)));
+        assert!(!filter.matches(0x200, 0x7301, &disassemble(0x7301)));
+        assert!(all().matches(0xFFF, 0x7301, &disassemble(0x7301)));
+    }
+
+    #[test]
+    fn keeps_the_last_lines_until_an_error() {
+        let (mut trace, out) = trace(all(), Some(2));
+        for cycle in 0..5 {
+            let pc = 0x200 + 2 * cycle as u16;
+            trace
+                .record(cycle, 0x1200, &state(pc), &state(0x200))
+                .unwrap();
+        }
+        assert_eq!(out.text(), "");
+        trace
+            .error(5, 0x20A, 0x0000, &ExecError::InvalidInstruction(0x0000))
+            .unwrap();
+        let text = out.text();
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert!(lines[0].starts_with("       3  206  1200"));
+        assert!(lines[1].starts_with("       4  208  1200"));
+        assert_eq!(
+            lines[2],
+            "       5  20A  0000  error: invalid instruction 0000"
+        );
+    }
+}
@@ -0,0 +1,451 @@
+use crate::audio::{Waveform, WAVEFORMS};
+use crate::chip::{Quirks, Rom, ETI660_PROGRAM_START, PLATFORMS, VIP_STACK_SLOTS};
+use crate::config::Settings;
+use crate::database;
+use crate::filter::{Pipeline, EFFECTS};
+use crate::metadata::RomMetadata;
+use crate::options::Options;
+use crate::palette::Palette;
+use crate::parse_address;
+use crate::persistence::{Persistence, MODES};
+use clap::{Args, Parser, Subcommand};
+use std::fs;
+use std::ops::RangeInclusive;
+
+#[derive(Parser)]
+#[command(name = "chip8", version, about = "CHIP-8 emulator")]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Command,
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Run a rom in a window (or headless for a number of frames)
+    Run {
+        rom: String,
+        #[command(flatten)]
+        machine: MachineArgs,
+        /// Run without a window for this many frames and print the screen
+        #[arg(long, value_name = "FRAMES")]
+        headless: Option<u64>,
+        /// Save the final screen as a PNG, named after the time by default
+        #[arg(long, value_name = "FILE")]
+        screenshot: Option<Option<String>>,
+        /// Record an animated GIF
+        #[arg(long, value_name = "FILE")]
+        record: Option<String>,
+        /// Record raw RGB24 frames at 60 fps, for muxing with the audio
+        #[arg(long, value_name = "FILE")]
+        record_frames: Option<String>,
+        /// Record the beeper as a WAV file
+        #[arg(long, value_name = "FILE")]
+        record_audio: Option<String>,
+        /// Screenshots and recordings at one pixel per chip 8 pixel, without filters
+        #[arg(long)]
+        native: bool,
+        /// Print when the beeper sounded, in frames, instead of the screen
+        #[arg(long, requires = "headless")]
+        tones: bool,
+        /// Reset and reload the rom whenever the file changes on disk
+        #[arg(long, conflicts_with = "headless")]
+        watch: bool,
+        /// Write every executed instruction with the registers it changed
+        #[arg(long, value_name = "FILE")]
+        trace: Option<String>,
+        /// Trace only instructions at these addresses: 200-2FF
+        #[arg(long, value_name = "RANGE", value_parser = address_range, requires = "trace")]
+        trace_range: Option<RangeInclusive<u16>>,
+        /// Trace only these opcode classes, first hex digits or mnemonics: D,CALL,RET
+        #[arg(
+            long,
+            value_name = "CLASSES",
+            value_delimiter = ',',
+            requires = "trace"
+        )]
+        trace_only: Vec<String>,
+        /// Keep only the last N instructions, written out when one fails
+        #[arg(long, value_name = "N", requires = "trace", value_parser = clap::value_parser!(u64).range(1..))]
+        trace_last: Option<u64>,
+    },
+    /// Pick roms from a directory and run them, one after the other
+    Browse {
+        #[arg(default_value = "roms")]
+        dir: String,
+        #[command(flatten)]
+        machine: MachineArgs,
+        /// List the roms on the terminal, each chosen rom runs in a window
+        #[arg(long)]
+        terminal: bool,
+    },
+    /// Print a rom as assembly
+    Disasm {
+        rom: String,
+        #[command(flatten)]
+        machine: MachineArgs,
+    },
+    /// Assemble a source file into a rom
+    Asm {
+        source: String,
+        /// Output rom file
+        #[arg(short, long)]
+        output: String,
+        /// Address the program will be loaded at
+        #[arg(long, value_parser = address, default_value = "0x200")]
+        origin: usize,
+    },
+    /// Show what the rom database knows about a rom
+    Info { rom: String },
+    /// Run roms headless and report execution errors or screen mismatches
+    Test {
+        #[arg(required = true)]
+        roms: Vec<String>,
+        #[command(flatten)]
+        machine: MachineArgs,
+        /// Frames to run each rom for
+        #[arg(long, default_value_t = 600)]
+        frames: u64,
+    },
+    /// Measure how fast a rom runs without a window
+    Bench {
+        rom: String,
+        #[command(flatten)]
+        machine: MachineArgs,
+        /// Frames to run
+        #[arg(long, default_value_t = 3600)]
+        frames: u64,
+    },
+    /// Run a rom against another emulator's trace and stop where they differ
+    Compare {
+        rom: String,
+        /// Reference trace, one `PC:0200 OP:00E0 I:0000 V0:00 ...` line per instruction
+        reference: String,
+        #[command(flatten)]
+        machine: MachineArgs,
+        /// Frames to run at most
+        #[arg(long, default_value_t = 3600)]
+        frames: u64,
+    },
+}
+
+// Settings shared by every subcommand that loads a rom
+#[derive(Args)]
+pub struct MachineArgs {
+    /// Read this configuration file instead of the user and project ones
+    #[arg(long, value_name = "FILE")]
+    config: Option<String>,
+    /// Window pixels per chip 8 pixel
+    #[arg(long, value_parser = clap::value_parser!(u32).range(1..))]
+    scale: Option<u32>,
+    /// Instructions per frame
+    #[arg(long, visible_alias = "tickrate", value_parser = clap::value_parser!(u32).range(1..))]
+    speed: Option<u32>,
+    /// Quirks profile
+    #[arg(long, value_parser = PLATFORMS)]
+    quirks: Option<String>,
+    /// Seed for the random number generator
+    #[arg(long)]
+    seed: Option<u64>,
+    /// Start in fullscreen
+    #[arg(long)]
+    fullscreen: bool,
+    /// Scale the window by whole multiples only, with bars around
+    #[arg(long)]
+    integer_scale: bool,
+    /// Start with the sound muted
+    #[arg(long)]
+    mute: bool,
+    /// Beeper waveform
+    #[arg(long, value_parser = WAVEFORMS)]
+    waveform: Option<String>,
+    /// Beeper frequency in Hz
+    #[arg(long, visible_alias = "frequency", value_parser = tone)]
+    tone: Option<f32>,
+    /// Beeper volume, 0.0 to 1.0
+    #[arg(long, value_parser = volume)]
+    volume: Option<f32>,
+    /// Theme name (purple, green, amber, lcd, high-contrast, octo, white)
+    /// or hex colours, background first: "#000000,#33FF66"
+    #[arg(long)]
+    palette: Option<String>,
+    /// Flicker reduction: off, phosphor[:frames], blend[:frames] or draw
+    #[arg(long, value_name = "MODE")]
+    persistence: Option<String>,
+    /// Image effects applied in order: none or a list of scale2x (epx),
+    /// scale3x, smooth, grid, scanlines and crt, e.g. "scale2x,scanlines"
+    #[arg(long, value_name = "EFFECTS")]
+    filter: Option<String>,
+    /// Show frame rate, instructions per second, pc and pressed keys
+    #[arg(long)]
+    hud: bool,
+    /// Show a clickable keypad with the host keys bound to each key
+    #[arg(long)]
+    keypad: bool,
+    /// Address the rom is loaded at (hex)
+    #[arg(long, visible_alias = "start", value_parser = address)]
+    load_address: Option<usize>,
+    /// Initial program counter (hex), defaults to the load address
+    #[arg(long, value_parser = address)]
+    entry: Option<usize>,
+    /// Load at 0x600 like the ETI 660
+    #[arg(long, conflicts_with = "load_address")]
+    eti660: bool,
+    /// Return stack depth, 12 on the VIP and 16 on SCHIP
+    #[arg(long, value_parser = clap::value_parser!(u16).range(1..))]
+    stack_depth: Option<u16>,
+    /// Keep the stack in ram at the VIP's address, 0xEA0, room for 24 entries
+    #[arg(long)]
+    stack_in_ram: bool,
+    /// Address of the built in font (hex)
+    #[arg(long, value_parser = address)]
+    font_address: Option<usize>,
+    /// Image copied into the interpreter area below the program
+    #[arg(long, value_name = "FILE")]
+    interpreter: Option<String>,
+    /// Reserve the VIP stack and display regions at the top of ram
+    #[arg(long)]
+    vip_layout: bool,
+    /// Make writes into the interpreter area an error
+    #[arg(long)]
+    protect_interpreter: bool,
+}
+
+impl MachineArgs {
+    // configuration files < rom database < per rom config sections
+    // < rom metadata file < command line
+    pub fn options(&self, rom_file: &str, rom: &Rom) -> Result<Options, String> {
+        let mut options = Options::default();
+
+        let settings = Settings::load(self.config.as_deref())?;
+        settings.apply(&mut options)?;
+
+        if let Some(entry) = database::lookup(&rom.sha1) {
+            if let Some(rate) = entry.rom().tickrate {
+                options.tickrate = rate;
+            }
+            if let Some(quirks) = entry.quirks() {
+                options.quirks = quirks;
+            }
+            if let Some(address) = entry.rom().start_address {
+                options.memory_map.load_address = address;
+            }
+            if let Some(palette) = entry.palette(&options.palette) {
+                options.palette = palette;
+            }
+            entry.bind_keys(&mut options.keymap);
+        }
+        for rom_settings in settings.for_rom(rom_file, &rom.sha1) {
+            rom_settings.apply(&mut options)?;
+        }
+
+        let metadata = RomMetadata::read(rom_file)?;
+        if let Some(address) = metadata.load_address {
+            options.memory_map.load_address = address;
+        }
+        options.memory_map.entry_point = metadata.entry_point;
+
+        self.apply(&mut options)?;
+        options.memory_map.validate()?;
+        if options.stack_in_ram && options.stack_depth > VIP_STACK_SLOTS {
+            return Err(format!(
+                "a stack in ram holds at most {} return addresses, not {}",
+                VIP_STACK_SLOTS, options.stack_depth
+            ));
+        }
+        Ok(options)
+    }
+
+    // configuration files < command line, for a window opened before
+    // any rom is chosen
+    pub fn base_options(&self) -> Result<Options, String> {
+        let mut options = Options::default();
+        Settings::load(self.config.as_deref())?.apply(&mut options)?;
+        self.apply(&mut options)?;
+        Ok(options)
+    }
+
+    fn apply(&self, options: &mut Options) -> Result<(), String> {
+        if let Some(scale) = self.scale {
+            options.scale = scale;
+        }
+        if let Some(speed) = self.speed {
+            options.tickrate = speed;
+        }
+        if let Some(name) = &self.quirks {
+            options.quirks = Quirks::for_platform(name).unwrap();
+        }
+        options.seed = self.seed.or(options.seed);
+        options.fullscreen |= self.fullscreen;
+        options.integer_scale |= self.integer_scale;
+        options.mute |= self.mute;
+        options.hud |= self.hud;
+        options.keypad |= self.keypad;
+        if let Some(name) = &self.waveform {
+            options.waveform = Waveform::parse(name).unwrap();
+        }
+        if let Some(tone) = self.tone {
+            options.tone = tone;
+        }
+        if let Some(volume) = self.volume {
+            options.volume = volume;
+        }
+        if let Some(text) = &self.palette {
+            options.palette = options.palette.parse(text).ok_or_else(|| {
+                format!(
+                    "--palette: expected a theme ({}) or 2 to 4 #RRGGBB colours, got `{}`",
+                    Palette::names().join(", "),
+                    text
+                )
+            })?;
+        }
+        if let Some(text) = &self.persistence {
+            options.persistence = Persistence::parse(text).ok_or_else(|| {
+                format!(
+                    "--persistence: expected one of {} with an optional :frames, got `{}`",
+                    MODES.join(", "),
+                    text
+                )
+            })?;
+        }
+        if let Some(text) = &self.filter {
+            options.filter = Pipeline::parse(text).ok_or_else(|| {
+                format!(
+                    "--filter: expected none or a list of {}, got `{}`",
+                    EFFECTS.join(", "),
+                    text
+                )
+            })?;
+        }
+        if self.eti660 {
+            options.memory_map.load_address = ETI660_PROGRAM_START;
+        }
+        if let Some(address) = self.load_address {
+            options.memory_map.load_address = address;
+        }
+        if let Some(address) = self.entry {
+            options.memory_map.entry_point = Some(address);
+        }
+        if let Some(depth) = self.stack_depth {
+            options.stack_depth = depth as usize;
+        }
+        options.stack_in_ram |= self.stack_in_ram;
+        if let Some(address) = self.font_address {
+            options.memory_map.font_address = address;
+        }
+        if let Some(file) = &self.interpreter {
+            let image = fs::read(file).map_err(|e| format!("{}: {}", file, e))?;
+            options.memory_map.interpreter = Some(image);
+        }
+        options.memory_map.vip_layout |= self.vip_layout;
+        options.memory_map.protect_interpreter |= self.protect_interpreter;
+        Ok(())
+    }
+}
+
+fn address(text: &str) -> Result<usize, String> {
+    parse_address(text).ok_or_else(|| format!("`{}` is not a hex address below 0x1000", text))
+}
+
+// "200-2FF", both ends included
+fn address_range(text: &str) -> Result<RangeInclusive<u16>, String> {
+    let (start, end) = text
+        .split_once('-')
+        .ok_or_else(|| format!("`{}` is not a range like 200-2FF", text))?;
+    let (start, end) = (address(start)? as u16, address(end)? as u16);
+    if start > end {
+        return Err(format!("`{}` ends before it starts", text));
+    }
+    Ok(start..=end)
+}
+
+fn tone(text: &str) -> Result<f32, String> {
+    match text.parse() {
+        Ok(tone) if (20.0..=20000.0).contains(&tone) => Ok(tone),
+        _ => Err("expected a frequency between 20 and 20000 Hz".to_string()),
+    }
+}
+
+fn volume(text: &str) -> Result<f32, String> {
+    match text.parse() {
+        Ok(volume) if (0.0..=1.0).contains(&volume) => Ok(volume),
+        _ => Err("expected a volume between 0.0 and 1.0".to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::{Path, PathBuf};
+
+    // a copy of BLINKY (a chip48 rom in the database) with a config file
+    // and a metadata file next to it
+    fn fixture(name: &str, config: &str, meta: Option<&str>) -> (PathBuf, PathBuf) {
+        let dir = std::env::temp_dir().join(format!("chip8-{}-{}", std::process::id(), name));
+        fs::create_dir_all(&dir).unwrap();
+        let rom_file = dir.join("BLINKY");
+        fs::copy("roms/BLINKY", &rom_file).unwrap();
+        let config_file = dir.join("chip8.toml");
+        fs::write(&config_file, config).unwrap();
+        if let Some(meta) = meta {
+            fs::write(RomMetadata::path_for(rom_file.to_str().unwrap()), meta).unwrap();
+        }
+        (rom_file, config_file)
+    }
+
+    fn options(rom_file: &Path, config_file: &Path, flags: &[&str]) -> Options {
+        let rom_file = rom_file.to_str().unwrap();
+        let args = ["chip8", "run", "--config", config_file.to_str().unwrap()];
+        let cli = Cli::try_parse_from(args.iter().chain(flags).chain(&[rom_file])).unwrap();
+        let machine = match cli.command {
+            Command::Run { machine, .. } => machine,
+            _ => unreachable!(),
+        };
+        let rom = Rom::read(&mut fs::File::open(rom_file).unwrap()).unwrap();
+        machine.options(rom_file, &rom).unwrap()
+    }
+
+    #[test]
+    fn rom_database_overrides_the_config_file() {
+        let (rom, config) = fixture("database", "quirks = \"xochip\"\nspeed = 7\n", None);
+        let options = options(&rom, &config, &[]);
+        assert_eq!(Some(options.quirks), Quirks::for_platform("chip48"));
+        assert_eq!(options.tickrate, 7);
+    }
+
+    #[test]
+    fn rom_sections_and_metadata_override_the_database() {
+        let config = "speed = 7\n[rom.BLINKY]\nspeed = 9\nquirks = \"superchip\"\n";
+        let (rom, config) = fixture("sections", config, Some("load_address = 0x300\n"));
+        let options = options(&rom, &config, &[]);
+        assert_eq!(Some(options.quirks), Quirks::for_platform("superchip"));
+        assert_eq!(options.tickrate, 9);
+        assert_eq!(options.memory_map.load_address, 0x300);
+    }
+
+    #[test]
+    fn command_line_wins() {
+        let config = "[rom.BLINKY]\nspeed = 9\n";
+        let (rom, config) = fixture("flags", config, Some("load_address = 0x300\n"));
+        let flags = [
+            "--speed",
+            "20",
+            "--quirks",
+            "modernChip8",
+            "--load-address",
+            "0x400",
+        ];
+        let options = options(&rom, &config, &flags);
+        assert_eq!(Some(options.quirks), Quirks::for_platform("modernChip8"));
+        assert_eq!(options.tickrate, 20);
+        assert_eq!(options.memory_map.load_address, 0x400);
+    }
+
+    #[test]
+    fn tone_and_volume_flags_are_range_checked() {
+        assert_eq!(tone("440"), Ok(440.0));
+        assert!(tone("10").is_err());
+        assert_eq!(volume("0.5"), Ok(0.5));
+        assert!(volume("1.5").is_err());
+        assert!(volume("loud").is_err());
+    }
+}
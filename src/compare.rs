@@ -0,0 +1,291 @@
+use crate::chip::{disassemble, Cpu, ExecError, State};
+use std::fs;
+
+// A reference trace is a text file with one line per executed
+// instruction, the state before it ran, as `key:value` or `key=value`
+// fields in hex:
+//
+//   # blank lines and lines starting with # are skipped
+//   PC:0200 OP:00E0 I:0000 DT:00 ST:00 V0:00 V1:00 ... VF:00
+//
+// Only PC is required. Keys are PC, OP, I, DT, ST and V0 to VF in any
+// case and order, values may start with 0x. Other keys, like a cycle
+// count or stack pointer, are ignored so most emulators' logs only need
+// their field names changed.
+pub struct Reference {
+    line: usize,
+    pc: u16,
+    opcode: Option<u16>,
+    index: Option<u16>,
+    delay_timer: Option<u8>,
+    sound_timer: Option<u8>,
+    registers: [Option<u8>; 16],
+}
+
+pub fn read(path: &str) -> Result<Vec<Reference>, String> {
+    let text = fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+    let mut references = Vec::new();
+    for (i, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let reference = parse(i + 1, line).map_err(|e| format!("{}:{}: {}", path, i + 1, e))?;
+        references.push(reference);
+    }
+    Ok(references)
+}
+
+fn parse(line: usize, text: &str) -> Result<Reference, String> {
+    let mut reference = Reference {
+        line,
+        pc: 0,
+        opcode: None,
+        index: None,
+        delay_timer: None,
+        sound_timer: None,
+        registers: [None; 16],
+    };
+    let mut pc = None;
+    for field in text.split_whitespace() {
+        let (key, value) = match field.split_once([':', '=']) {
+            Some(pair) => pair,
+            None => return Err(format!("expected key:value, got `{}`", field)),
+        };
+        let key = key.to_ascii_uppercase();
+        let digits = value.trim_start_matches("0x").trim_start_matches("0X");
+        let value = u16::from_str_radix(digits, 16);
+        let number = |max: u16| match value {
+            Ok(value) if value <= max => Ok(value),
+            _ => Err(format!(
+                "{} should be hex up to {:X}, got `{}`",
+                key, max, digits
+            )),
+        };
+        match key.as_str() {
+            "PC" => pc = Some(number(0xFFF)?),
+            "OP" => reference.opcode = Some(number(0xFFFF)?),
+            "I" => reference.index = Some(number(0xFFFF)?),
+            "DT" => reference.delay_timer = Some(number(0xFF)? as u8),
+            "ST" => reference.sound_timer = Some(number(0xFF)? as u8),
+            _ => {
+                let register = key
+                    .strip_prefix('V')
+                    .filter(|digit| digit.len() == 1)
+                    .and_then(|digit| usize::from_str_radix(digit, 16).ok());
+                if let Some(register) = register {
+                    reference.registers[register] = Some(number(0xFF)? as u8);
+                }
+            }
+        }
+    }
+    reference.pc = pc.ok_or("no PC field")?;
+    Ok(reference)
+}
+
+impl Reference {
+    // name, ours, theirs; fields the reference leaves out are not compared
+    fn rows(&self, ours: &State, opcode: u16) -> Vec<(String, String, Option<String>)> {
+        let mut rows = vec![
+            row("PC", 3, ours.pc, Some(self.pc)),
+            row("OP", 4, opcode, self.opcode),
+            row("I", 3, ours.index, self.index),
+            row(
+                "DT",
+                2,
+                ours.delay_timer as u16,
+                self.delay_timer.map(u16::from),
+            ),
+            row(
+                "ST",
+                2,
+                ours.sound_timer as u16,
+                self.sound_timer.map(u16::from),
+            ),
+        ];
+        for (i, (our, their)) in ours.registers.iter().zip(&self.registers).enumerate() {
+            let name = format!("V{:X}", i);
+            rows.push(row(&name, 2, *our as u16, their.map(u16::from)));
+        }
+        rows
+    }
+
+    fn matches(&self, ours: &State, opcode: u16) -> bool {
+        self.rows(ours, opcode)
+            .iter()
+            .all(|(_, our, their)| their.as_ref().is_none_or(|their| their == our))
+    }
+}
+
+fn row(
+    name: &str,
+    width: usize,
+    ours: u16,
+    theirs: Option<u16>,
+) -> (String, String, Option<String>) {
+    let hex = |value: u16| format!("{:0width$X}", value, width = width);
+    (name.to_string(), hex(ours), theirs.map(hex))
+}
+
+// Run the cpu against the reference one instruction at a time, for at
+// most `frames` frames. At the first instruction whose state differs,
+// both states are printed side by side. True when the whole reference
+// matched, false when it differs or the frames ran out before its end.
+pub fn run(cpu: &mut Cpu, reference: &[Reference], frames: u64) -> Result<bool, ExecError> {
+    let mut next = 0;
+    let mut previous: Option<(u16, u16)> = None; // pc and opcode
+    let mut diverged = None;
+    for _ in 0..frames {
+        let mut check = |opcode: u16, state: &State| {
+            let expected = match reference.get(next) {
+                Some(expected) => expected,
+                None => return false,
+            };
+            if !expected.matches(state, opcode) {
+                diverged = Some((state.clone(), opcode));
+                return false;
+            }
+            next += 1;
+            previous = Some((state.pc, opcode));
+            true
+        };
+        if !cpu.run_frame_checked(&mut check)? {
+            break;
+        }
+    }
+
+    let (state, opcode) = match diverged {
+        Some(diverged) => diverged,
+        None => {
+            // a reference cut short by --frames was not checked to the end
+            return Ok(match reference.get(next) {
+                None => {
+                    println!("all {} instructions of the reference match", next);
+                    true
+                }
+                Some(expected) => {
+                    println!(
+                        "{} instructions match, stopped after {} frames at reference line {}",
+                        next, frames, expected.line
+                    );
+                    false
+                }
+            });
+        }
+    };
+    let expected = &reference[next];
+    println!(
+        "diverged at instruction {}, reference line {}",
+        next + 1,
+        expected.line
+    );
+    match previous {
+        Some((pc, opcode)) => println!("after {:03X}  {:04X}  {}", pc, opcode, disassemble(opcode)),
+        None => println!("before the first instruction"),
+    }
+    println!();
+    println!("      ours  reference");
+    for (name, our, their) in expected.rows(&state, opcode) {
+        let (their, mark) = match their {
+            Some(their) if their != our => (their, "  <"),
+            Some(their) => (their, ""),
+            None => ("-".to_string(), ""),
+        };
+        let line = format!("{:<4}  {:<4}  {:<4}{}", name, our, their, mark);
+        println!("{}", line.trim_end());
+    }
+    Ok(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chip::Rom;
+    use crate::options::Options;
+
+    fn state() -> State {
+        let mut registers = [0; 16];
+        registers[3] = 0x05;
+        State {
+            pc: 0x21A,
+            registers,
+            index: 0x2A0,
+            delay_timer: 0x10,
+            sound_timer: 0,
+        }
+    }
+
+    #[test]
+    fn reads_every_field() {
+        let reference = parse(7, "PC:021A OP:7301 I:02A0 DT:10 ST:00 V3:05 VF:01").unwrap();
+        assert_eq!(reference.line, 7);
+        assert_eq!(reference.pc, 0x21A);
+        assert_eq!(reference.opcode, Some(0x7301));
+        assert_eq!(reference.index, Some(0x2A0));
+        assert_eq!(reference.delay_timer, Some(0x10));
+        assert_eq!(reference.sound_timer, Some(0));
+        assert_eq!(reference.registers[3], Some(5));
+        assert_eq!(reference.registers[0xF], Some(1));
+        assert_eq!(reference.registers[0], None);
+    }
+
+    #[test]
+    fn accepts_any_case_equals_and_0x() {
+        let reference = parse(1, "pc=0x21a v3=0X05 i:2a0").unwrap();
+        assert_eq!(reference.pc, 0x21A);
+        assert_eq!(reference.registers[3], Some(5));
+        assert_eq!(reference.index, Some(0x2A0));
+    }
+
+    #[test]
+    fn ignores_unknown_keys() {
+        let reference = parse(1, "CYC:1234 PC:200 SP:0 V10:99").unwrap();
+        assert_eq!(reference.pc, 0x200);
+        assert!(reference.registers.iter().all(Option::is_none));
+    }
+
+    #[test]
+    fn rejects_bad_lines() {
+        assert_eq!(parse(1, "V0:00").err().unwrap(), "no PC field");
+        assert_eq!(
+            parse(1, "PC 200").err().unwrap(),
+            "expected key:value, got `PC`"
+        );
+        assert_eq!(
+            parse(1, "PC:1000").err().unwrap(),
+            "PC should be hex up to FFF, got `1000`"
+        );
+        assert_eq!(
+            parse(1, "PC:200 V0:zz").err().unwrap(),
+            "V0 should be hex up to FF, got `zz`"
+        );
+    }
+
+    #[test]
+    fn compares_only_the_fields_given() {
+        let ours = state();
+        assert!(parse(1, "PC:21A V3:05").unwrap().matches(&ours, 0x7301));
+        assert!(parse(1, "PC:21A OP:7301 DT:10")
+            .unwrap()
+            .matches(&ours, 0x7301));
+        assert!(!parse(1, "PC:21A V3:06").unwrap().matches(&ours, 0x7301));
+        assert!(!parse(1, "PC:21A OP:7302").unwrap().matches(&ours, 0x7301));
+        assert!(!parse(1, "PC:21C").unwrap().matches(&ours, 0x7301));
+    }
+
+    #[test]
+    fn the_whole_reference_must_be_reached() {
+        // JP 0x200, 16 instructions a frame
+        let mut cpu = Cpu::new(&Options::default());
+        let rom = Rom {
+            data: vec![0x12, 0x00],
+            sha1: String::new(),
+        };
+        cpu.load(&rom).unwrap();
+        let reference: Vec<Reference> = (1..=20)
+            .map(|line| parse(line, "PC:200").unwrap())
+            .collect();
+        assert!(run(&mut cpu, &reference[..16], 1).unwrap());
+        assert!(!run(&mut cpu, &reference, 1).unwrap());
+    }
+}
@@ -0,0 +1,440 @@
+use crate::audio::{Waveform, WAVEFORMS};
+use crate::chip::{Quirks, PLATFORMS};
+use crate::filter::{Pipeline, EFFECTS};
+use crate::options::Options;
+use crate::palette::{parse_color, Palette};
+use crate::persistence::{Persistence, MODES};
+use sdl2::keyboard::Keycode;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// Project level configuration, looked up in the working directory
+const PROJECT_CONFIG: &str = "chip8.toml";
+
+// Settings read from a TOML configuration file:
+//
+//   speed = 16              # instructions per frame
+//   quirks = "superchip"
+//
+//   [display]
+//   scale = 12
+//   fullscreen = false
+//   integer_scale = true    # whole multiples only, otherwise fill the window
+//   palette = "amber"       # built in theme, colours below override it
+//   foreground = "#33FF66"
+//   background = "#000000"
+//   persistence = "phosphor:4"  # off, phosphor[:frames], blend[:frames] or draw
+//   filter = "scale2x,scanlines"
+//   hud = false             # fps, ips, pc and keys over the game
+//   keypad = false          # clickable keypad beside the game
+//
+//   [audio]
+//   volume = 0.25
+//   frequency = 440
+//   waveform = "square"     # square, triangle, sine or buzz
+//   attack = 2              # fade in and out, milliseconds
+//   release = 5
+//   mute = false
+//
+//   [keymap]                # host key name = chip 8 key
+//   W = 5
+//
+//   [hotkeys]
+//   quit = "Q"
+//   cycle_palette = "P"
+//   cycle_filter = "F3"
+//   fullscreen = "F11"
+//   screenshot = "F12"
+//   screenshot_native = "F9"
+//   record = "F7"
+//   mute = "M"
+//   hud = "F1"
+//   keypad = "F2"
+//   menu = "Escape"         # back to the launcher
+//   reset = "F5"            # from the entry point, ram kept
+//   hard_reset = "F6"       # as if the rom was just loaded
+//   reload = "F8"           # read the rom file again
+//   pause = "F4"
+//   memory = "F10"          # hex dump and editor, pauses while open
+//   sprites = "I"           # ram as sprites, pauses while open
+//
+//   [rom.INVADERS]          # per rom overrides, by file name or SHA-1
+//   speed = 30
+#[derive(Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+pub struct Settings {
+    speed: Option<u32>,
+    quirks: Option<String>,
+    #[serde(default)]
+    display: DisplaySettings,
+    #[serde(default)]
+    audio: AudioSettings,
+    #[serde(default)]
+    keymap: BTreeMap<String, u8>,
+    #[serde(default)]
+    hotkeys: BTreeMap<String, String>,
+    #[serde(default)]
+    rom: BTreeMap<String, Settings>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+struct DisplaySettings {
+    scale: Option<u32>,
+    fullscreen: Option<bool>,
+    integer_scale: Option<bool>,
+    palette: Option<String>,
+    foreground: Option<String>,
+    background: Option<String>,
+    persistence: Option<String>,
+    filter: Option<String>,
+    hud: Option<bool>,
+    keypad: Option<bool>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+struct AudioSettings {
+    volume: Option<f32>,
+    frequency: Option<f32>,
+    waveform: Option<String>,
+    attack: Option<f32>,
+    release: Option<f32>,
+    mute: Option<bool>,
+}
+
+impl Settings {
+    // Read the configuration. An explicit file replaces the user and
+    // project files, otherwise both are read and the project file wins.
+    pub fn load(explicit: Option<&str>) -> Result<Self, String> {
+        let files = match explicit {
+            Some(file) => vec![PathBuf::from(file)],
+            None => user_config()
+                .into_iter()
+                .chain(Some(PathBuf::from(PROJECT_CONFIG)))
+                .filter(|path| path.exists())
+                .collect(),
+        };
+
+        let mut settings = Self::default();
+        for path in files {
+            settings.merge(Self::read(&path)?);
+        }
+        Ok(settings)
+    }
+
+    fn read(path: &Path) -> Result<Self, String> {
+        let text = fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        let settings: Self =
+            toml::from_str(&text).map_err(|e| format!("{}: {}", path.display(), e))?;
+        settings
+            .validate("")
+            .map_err(|e| format!("{}: {}", path.display(), e))?;
+        Ok(settings)
+    }
+
+    // check every value by applying it to a scratch set of options
+    fn validate(&self, prefix: &str) -> Result<(), String> {
+        self.apply_with_prefix(&mut Options::default(), prefix)?;
+        for (name, settings) in &self.rom {
+            let prefix = format!("rom.{}.", name);
+            if !settings.rom.is_empty() {
+                return Err(format!("{}rom: rom sections can not be nested", prefix));
+            }
+            settings.validate(&prefix)?;
+        }
+        Ok(())
+    }
+
+    // later files override earlier ones key by key
+    fn merge(&mut self, other: Self) {
+        self.speed = other.speed.or(self.speed);
+        if other.quirks.is_some() {
+            self.quirks = other.quirks;
+        }
+        self.display.scale = other.display.scale.or(self.display.scale);
+        self.display.fullscreen = other.display.fullscreen.or(self.display.fullscreen);
+        self.display.integer_scale = other.display.integer_scale.or(self.display.integer_scale);
+        if other.display.palette.is_some() {
+            self.display.palette = other.display.palette;
+        }
+        if other.display.foreground.is_some() {
+            self.display.foreground = other.display.foreground;
+        }
+        if other.display.background.is_some() {
+            self.display.background = other.display.background;
+        }
+        if other.display.persistence.is_some() {
+            self.display.persistence = other.display.persistence;
+        }
+        if other.display.filter.is_some() {
+            self.display.filter = other.display.filter;
+        }
+        self.display.hud = other.display.hud.or(self.display.hud);
+        self.display.keypad = other.display.keypad.or(self.display.keypad);
+        self.audio.volume = other.audio.volume.or(self.audio.volume);
+        self.audio.frequency = other.audio.frequency.or(self.audio.frequency);
+        if other.audio.waveform.is_some() {
+            self.audio.waveform = other.audio.waveform;
+        }
+        self.audio.attack = other.audio.attack.or(self.audio.attack);
+        self.audio.release = other.audio.release.or(self.audio.release);
+        self.audio.mute = other.audio.mute.or(self.audio.mute);
+        self.keymap.extend(other.keymap);
+        self.hotkeys.extend(other.hotkeys);
+        for (name, settings) in other.rom {
+            self.rom.entry(name).or_default().merge(settings);
+        }
+    }
+
+    // per rom sections matching the file name or the hash, in that order
+    pub fn for_rom(&self, rom_file: &str, sha1: &str) -> Vec<&Settings> {
+        let file_name = Path::new(rom_file)
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        [file_name.as_str(), sha1]
+            .iter()
+            .filter_map(|key| self.rom.get(*key))
+            .collect()
+    }
+
+    pub fn apply(&self, options: &mut Options) -> Result<(), String> {
+        self.apply_with_prefix(options, "")
+    }
+
+    // errors name the offending key, e.g. `display.foreground`
+    fn apply_with_prefix(&self, options: &mut Options, prefix: &str) -> Result<(), String> {
+        if let Some(speed) = self.speed {
+            if speed == 0 {
+                return Err(format!("{}speed: must be at least 1", prefix));
+            }
+            options.tickrate = speed;
+        }
+        if let Some(name) = &self.quirks {
+            options.quirks = Quirks::for_platform(name).ok_or_else(|| {
+                format!(
+                    "{}quirks: unknown profile `{}`, expected one of {}",
+                    prefix,
+                    name,
+                    PLATFORMS.join(", ")
+                )
+            })?;
+        }
+        if let Some(scale) = self.display.scale {
+            if scale == 0 {
+                return Err(format!("{}display.scale: must be at least 1", prefix));
+            }
+            options.scale = scale;
+        }
+        if let Some(fullscreen) = self.display.fullscreen {
+            options.fullscreen = fullscreen;
+        }
+        if let Some(integer_scale) = self.display.integer_scale {
+            options.integer_scale = integer_scale;
+        }
+        if let Some(name) = &self.display.palette {
+            options.palette = Palette::named(name).ok_or_else(|| {
+                format!(
+                    "{}display.palette: unknown theme `{}`, expected one of {}",
+                    prefix,
+                    name,
+                    Palette::names().join(", ")
+                )
+            })?;
+        }
+        if let Some(text) = &self.display.background {
+            let color = parse_color(text).ok_or_else(|| {
+                format!(
+                    "{}display.background: expected #RRGGBB, got `{}`",
+                    prefix, text
+                )
+            })?;
+            options.palette = options
+                .palette
+                .with_colors(&[color, options.palette.colors[1]])
+                .unwrap();
+        }
+        if let Some(text) = &self.display.foreground {
+            let color = parse_color(text).ok_or_else(|| {
+                format!(
+                    "{}display.foreground: expected #RRGGBB, got `{}`",
+                    prefix, text
+                )
+            })?;
+            options.palette = options
+                .palette
+                .with_colors(&[options.palette.colors[0], color])
+                .unwrap();
+        }
+        if let Some(text) = &self.display.persistence {
+            options.persistence = Persistence::parse(text).ok_or_else(|| {
+                format!(
+                    "{}display.persistence: expected one of {} with an optional :frames, got `{}`",
+                    prefix,
+                    MODES.join(", "),
+                    text
+                )
+            })?;
+        }
+        if let Some(text) = &self.display.filter {
+            options.filter = Pipeline::parse(text).ok_or_else(|| {
+                format!(
+                    "{}display.filter: expected none or a list of {}, got `{}`",
+                    prefix,
+                    EFFECTS.join(", "),
+                    text
+                )
+            })?;
+        }
+        if let Some(hud) = self.display.hud {
+            options.hud = hud;
+        }
+        if let Some(keypad) = self.display.keypad {
+            options.keypad = keypad;
+        }
+        if let Some(volume) = self.audio.volume {
+            if !(0.0..=1.0).contains(&volume) {
+                return Err(format!(
+                    "{}audio.volume: must be between 0.0 and 1.0",
+                    prefix
+                ));
+            }
+            options.volume = volume;
+        }
+        if let Some(frequency) = self.audio.frequency {
+            if !(20.0..=20000.0).contains(&frequency) {
+                return Err(format!(
+                    "{}audio.frequency: must be between 20 and 20000 Hz",
+                    prefix
+                ));
+            }
+            options.tone = frequency;
+        }
+        if let Some(name) = &self.audio.waveform {
+            options.waveform = Waveform::parse(name).ok_or_else(|| {
+                format!(
+                    "{}audio.waveform: unknown waveform `{}`, expected one of {}",
+                    prefix,
+                    name,
+                    WAVEFORMS.join(", ")
+                )
+            })?;
+        }
+        for (key, value, field) in [
+            ("attack", self.audio.attack, &mut options.attack),
+            ("release", self.audio.release, &mut options.release),
+        ] {
+            if let Some(ms) = value {
+                if !(0.0..=1000.0).contains(&ms) {
+                    return Err(format!(
+                        "{}audio.{}: must be between 0 and 1000 ms",
+                        prefix, key
+                    ));
+                }
+                *field = ms;
+            }
+        }
+        if let Some(mute) = self.audio.mute {
+            options.mute = mute;
+        }
+        for (name, key) in &self.keymap {
+            let keycode = Keycode::from_name(name)
+                .ok_or_else(|| format!("{}keymap.{}: unknown key name", prefix, name))?;
+            if *key > 0xF {
+                return Err(format!(
+                    "{}keymap.{}: chip 8 keys are 0 to 15",
+                    prefix, name
+                ));
+            }
+            options.keymap.bind(keycode, *key);
+        }
+        for (name, key) in &self.hotkeys {
+            let keycode = Keycode::from_name(key)
+                .ok_or_else(|| format!("{}hotkeys.{}: unknown key name `{}`", prefix, name, key))?;
+            if !options.hotkeys.set(name, keycode) {
+                return Err(format!("{}hotkeys.{}: unknown hotkey", prefix, name));
+            }
+        }
+        Ok(())
+    }
+}
+
+// $XDG_CONFIG_HOME/chip8/config.toml or ~/.config/chip8/config.toml
+fn user_config() -> Option<PathBuf> {
+    let base = env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".config")))?;
+    Some(base.join("chip8").join("config.toml"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(text: &str) -> Result<Settings, String> {
+        let settings: Settings = toml::from_str(text).map_err(|e| e.to_string())?;
+        settings.validate("")?;
+        Ok(settings)
+    }
+
+    fn error(text: &str) -> String {
+        match parse(text) {
+            Ok(_) => panic!("accepted {:?}", text),
+            Err(e) => e,
+        }
+    }
+
+    #[test]
+    fn applies_values_to_the_options() {
+        let settings = parse(
+            "speed = 30\nquirks = \"chip48\"\n[display]\nscale = 8\nforeground = \"#33FF66\"\n",
+        )
+        .unwrap();
+        let mut options = Options::default();
+        settings.apply(&mut options).unwrap();
+        assert_eq!(options.tickrate, 30);
+        assert_eq!(options.scale, 8);
+        assert_eq!(Some(options.quirks), Quirks::for_platform("chip48"));
+        assert_eq!(options.palette.colors[1], parse_color("#33FF66").unwrap());
+    }
+
+    #[test]
+    fn errors_name_the_key_at_fault() {
+        assert_eq!(
+            error("[rom.INVADERS.display]\nforeground = \"green\"\n"),
+            "rom.INVADERS.display.foreground: expected #RRGGBB, got `green`"
+        );
+        assert_eq!(error("speed = 0\n"), "speed: must be at least 1");
+        assert_eq!(
+            error("[audio]\nvolume = 2.0\n"),
+            "audio.volume: must be between 0.0 and 1.0"
+        );
+        assert!(error("quirks = \"pdp8\"\n").starts_with("quirks: unknown profile `pdp8`"));
+        assert!(error("[display]\npalette = \"plaid\"\n")
+            .starts_with("display.palette: unknown theme `plaid`"));
+        assert!(parse("[rom.A.rom.B]\nspeed = 1\n").is_err());
+        assert!(parse("sped = 1\n").is_err());
+    }
+
+    #[test]
+    fn later_files_override_key_by_key() {
+        let mut settings = parse("speed = 10\n[display]\nscale = 4\n").unwrap();
+        settings.merge(parse("speed = 20\n[rom.PONG]\nspeed = 5\n").unwrap());
+        assert_eq!(settings.speed, Some(20));
+        assert_eq!(settings.display.scale, Some(4));
+        assert_eq!(settings.rom["PONG"].speed, Some(5));
+    }
+
+    #[test]
+    fn rom_sections_match_file_name_then_hash() {
+        let settings = parse("[rom.PONG]\nspeed = 5\n[rom.abc]\nspeed = 6\n").unwrap();
+        let sections = settings.for_rom("roms/PONG", "abc");
+        let speeds: Vec<_> = sections.iter().map(|s| s.speed).collect();
+        assert_eq!(speeds, [Some(5), Some(6)]);
+        assert!(settings.for_rom("roms/BRIX", "def").is_empty());
+    }
+}
@@ -0,0 +1,164 @@
+use crate::chip::Quirks;
+use crate::frontend::Keymap;
+use crate::palette::{parse_color, Palette};
+use serde::Deserialize;
+use std::collections::HashMap;
+
+// Bundled subset of the community CHIP-8 database
+// (https://github.com/chip-8/chip-8-database), same file layout:
+// sha1-hashes.json maps a rom hash to an index into programs.json.
+const HASHES: &str = include_str!("../database/sha1-hashes.json");
+const PROGRAMS: &str = include_str!("../database/programs.json");
+
+#[derive(Deserialize)]
+pub struct Program {
+    pub title: String,
+    pub description: Option<String>,
+    pub release: Option<String>,
+    #[serde(default)]
+    pub authors: Vec<String>,
+    roms: HashMap<String, RomEntry>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RomEntry {
+    pub file: Option<String>,
+    #[serde(default)]
+    pub platforms: Vec<String>,
+    pub tickrate: Option<u32>,
+    pub start_address: Option<usize>,
+    #[serde(default)]
+    pub quirky_platforms: HashMap<String, HashMap<String, bool>>,
+    #[serde(default)]
+    pub keys: HashMap<String, u8>,
+    pub colors: Option<Colors>,
+}
+
+#[derive(Deserialize)]
+pub struct Colors {
+    #[serde(default)]
+    pub pixels: Vec<String>, // background first, then foreground
+}
+
+// What the database knows about one rom
+pub struct Entry {
+    pub program: Program,
+    pub sha1: String,
+}
+
+impl Entry {
+    pub fn rom(&self) -> &RomEntry {
+        &self.program.roms[&self.sha1]
+    }
+
+    // first listed platform is the one the rom was written for
+    pub fn platform(&self) -> Option<&str> {
+        self.rom().platforms.first().map(String::as_str)
+    }
+
+    // platform defaults with the rom specific overrides applied
+    pub fn quirks(&self) -> Option<Quirks> {
+        let platform = self.platform()?;
+        let mut quirks = Quirks::for_platform(platform)?;
+        if let Some(overrides) = self.rom().quirky_platforms.get(platform) {
+            for (name, value) in overrides {
+                quirks.set(name, *value);
+            }
+        }
+        Some(quirks)
+    }
+
+    pub fn bind_keys(&self, keymap: &mut Keymap) {
+        for (name, key) in &self.rom().keys {
+            keymap.bind_named(name, *key);
+        }
+    }
+
+    // pixel colours in plane order, background first
+    pub fn palette(&self, base: &Palette) -> Option<Palette> {
+        let pixels = &self.rom().colors.as_ref()?.pixels;
+        let colors = pixels
+            .iter()
+            .take(4)
+            .map(|color| parse_color(color))
+            .collect::<Option<Vec<_>>>()?;
+        base.with_colors(&colors)
+    }
+
+    pub fn print(&self) {
+        let program = &self.program;
+        let rom = self.rom();
+        println!("title:    {}", program.title);
+        if !program.authors.is_empty() {
+            println!("authors:  {}", program.authors.join(", "));
+        }
+        if let Some(release) = &program.release {
+            println!("release:  {}", release);
+        }
+        if let Some(description) = &program.description {
+            println!("about:    {}", description);
+        }
+        if let Some(file) = &rom.file {
+            println!("file:     {}", file);
+        }
+        println!("sha1:     {}", self.sha1);
+        println!("platform: {}", rom.platforms.join(", "));
+        if let Some(tickrate) = rom.tickrate {
+            println!("tickrate: {}", tickrate);
+        }
+        if let Some(address) = rom.start_address {
+            println!("start:    {:#05X}", address);
+        }
+        if let Some(quirks) = self.quirks() {
+            println!("quirks:   {:?}", quirks);
+        }
+        let mut keys: Vec<_> = rom.keys.iter().collect();
+        keys.sort();
+        for (name, key) in keys {
+            println!("key:      {} -> {:X}", name, key);
+        }
+        if let Some(colors) = &rom.colors {
+            println!("colors:   {}", colors.pixels.join(" "));
+        }
+    }
+}
+
+pub fn lookup(sha1: &str) -> Option<Entry> {
+    let hashes: HashMap<String, usize> =
+        serde_json::from_str(HASHES).expect("bundled sha1-hashes.json is invalid");
+    let index = *hashes.get(sha1)?;
+    let mut programs: Vec<Program> =
+        serde_json::from_str(PROGRAMS).expect("bundled programs.json is invalid");
+    if index >= programs.len() || !programs[index].roms.contains_key(sha1) {
+        return None;
+    }
+    Some(Entry {
+        program: programs.swap_remove(index),
+        sha1: sha1.to_string(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sdl2::keyboard::Keycode;
+
+    #[test]
+    fn finds_bundled_roms_by_hash() {
+        let entry = lookup("d40abc54374e4343639f993e897e00904ddf85d9").unwrap();
+        assert_eq!(entry.program.title, "Blinky");
+        assert_eq!(entry.platform(), Some("chip48"));
+        assert_eq!(entry.quirks(), Quirks::for_platform("chip48"));
+        assert!(lookup("0000000000000000000000000000000000000000").is_none());
+    }
+
+    #[test]
+    fn binds_the_named_keys_of_a_rom() {
+        let entry = lookup("f13766c14aeb02ad8d4d103cb5eadd282d20cddc").unwrap();
+        let mut keymap = Keymap::default();
+        entry.bind_keys(&mut keymap);
+        assert_eq!(keymap.get(Keycode::Left), Some(4));
+        assert_eq!(keymap.get(Keycode::Right), Some(6));
+    }
+}
@@ -0,0 +1,337 @@
+use crate::image::Image;
+use sdl2::pixels::Color;
+
+pub const EFFECTS: [&str; 7] = [
+    "scale2x",
+    "epx",
+    "scale3x",
+    "smooth",
+    "grid",
+    "scanlines",
+    "crt",
+];
+
+// Software image effects, applied on the cpu so every output (window,
+// screenshots, recordings) looks the same
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Effect {
+    Scale2x, // also known as EPX
+    Scale3x,
+    Smooth,    // 3x3 blur, best after a scaler
+    Grid,      // 4x, darkened cell borders like an LCD
+    Scanlines, // 2x, every other row darkened
+    Crt,       // 3x, scanlines plus an RGB aperture mask
+}
+
+impl Effect {
+    fn parse(name: &str) -> Option<Self> {
+        let effect = match name {
+            "scale2x" | "epx" => Effect::Scale2x,
+            "scale3x" => Effect::Scale3x,
+            "smooth" => Effect::Smooth,
+            "grid" => Effect::Grid,
+            "scanlines" => Effect::Scanlines,
+            "crt" => Effect::Crt,
+            _ => return None,
+        };
+        Some(effect)
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            Effect::Scale2x => "scale2x",
+            Effect::Scale3x => "scale3x",
+            Effect::Smooth => "smooth",
+            Effect::Grid => "grid",
+            Effect::Scanlines => "scanlines",
+            Effect::Crt => "crt",
+        }
+    }
+
+    fn apply(&self, image: &Image) -> Image {
+        match self {
+            Effect::Scale2x => scale2x(image),
+            Effect::Scale3x => scale3x(image),
+            Effect::Smooth => smooth(image),
+            Effect::Grid => grid(image),
+            Effect::Scanlines => scanlines(image),
+            Effect::Crt => crt(image),
+        }
+    }
+}
+
+// hotkey order, each preset replaces the whole pipeline
+const PRESETS: [&[Effect]; 7] = [
+    &[],
+    &[Effect::Scale2x],
+    &[Effect::Scale3x],
+    &[Effect::Scale3x, Effect::Smooth],
+    &[Effect::Grid],
+    &[Effect::Scanlines],
+    &[Effect::Scale2x, Effect::Crt],
+];
+
+// Effects applied in order
+#[derive(Clone, Default, PartialEq, Debug)]
+pub struct Pipeline {
+    effects: Vec<Effect>,
+}
+
+impl Pipeline {
+    // comma separated effect names, "none" for the plain image:
+    // "scale2x,scanlines"
+    pub fn parse(text: &str) -> Option<Self> {
+        if text == "none" {
+            return Some(Self::default());
+        }
+        let effects = text
+            .split(',')
+            .map(|name| Effect::parse(name.trim()))
+            .collect::<Option<_>>()?;
+        Some(Self { effects })
+    }
+
+    // the preset after this one, wrapping around
+    pub fn next(&self) -> Self {
+        let index = PRESETS
+            .iter()
+            .position(|effects| *effects == self.effects.as_slice())
+            .map_or(0, |index| (index + 1) % PRESETS.len());
+        Self {
+            effects: PRESETS[index].to_vec(),
+        }
+    }
+
+    // in the syntax `parse` reads
+    pub fn name(&self) -> String {
+        if self.effects.is_empty() {
+            return "none".to_string();
+        }
+        let names: Vec<&str> = self.effects.iter().map(|effect| effect.name()).collect();
+        names.join(",")
+    }
+
+    pub fn apply(&self, image: Image) -> Image {
+        self.effects
+            .iter()
+            .fold(image, |image, effect| effect.apply(&image))
+    }
+}
+
+fn scale2x(image: &Image) -> Image {
+    let mut output = Image::new(image.width * 2, image.height * 2, Color::BLACK);
+    for y in 0..image.height {
+        for x in 0..image.width {
+            let (xi, yi) = (x as isize, y as isize);
+            let p = image.get(xi, yi);
+            let a = image.get(xi, yi - 1);
+            let b = image.get(xi + 1, yi);
+            let c = image.get(xi - 1, yi);
+            let d = image.get(xi, yi + 1);
+
+            let pick = |edge: bool, color: Color| if edge { color } else { p };
+            let (ox, oy) = (x * 2, y * 2);
+            output.set(ox, oy, pick(c == a && c != d && a != b, a));
+            output.set(ox + 1, oy, pick(a == b && a != c && b != d, b));
+            output.set(ox, oy + 1, pick(d == c && d != b && c != a, c));
+            output.set(ox + 1, oy + 1, pick(b == d && b != a && d != c, d));
+        }
+    }
+    output
+}
+
+fn scale3x(image: &Image) -> Image {
+    let mut output = Image::new(image.width * 3, image.height * 3, Color::BLACK);
+    for y in 0..image.height {
+        for x in 0..image.width {
+            let (xi, yi) = (x as isize, y as isize);
+            // a b c
+            // d e f
+            // g h i
+            let a = image.get(xi - 1, yi - 1);
+            let b = image.get(xi, yi - 1);
+            let c = image.get(xi + 1, yi - 1);
+            let d = image.get(xi - 1, yi);
+            let e = image.get(xi, yi);
+            let f = image.get(xi + 1, yi);
+            let g = image.get(xi - 1, yi + 1);
+            let h = image.get(xi, yi + 1);
+            let i = image.get(xi + 1, yi + 1);
+
+            let block = if b != h && d != f {
+                let pick = |edge: bool, color: Color| if edge { color } else { e };
+                [
+                    pick(d == b, d),
+                    pick((d == b && e != c) || (b == f && e != a), b),
+                    pick(b == f, f),
+                    pick((d == b && e != g) || (d == h && e != a), d),
+                    e,
+                    pick((b == f && e != i) || (h == f && e != c), f),
+                    pick(d == h, d),
+                    pick((d == h && e != i) || (h == f && e != g), h),
+                    pick(h == f, f),
+                ]
+            } else {
+                [e; 9]
+            };
+            for (n, color) in block.iter().enumerate() {
+                output.set(x * 3 + n % 3, y * 3 + n / 3, *color);
+            }
+        }
+    }
+    output
+}
+
+fn smooth(image: &Image) -> Image {
+    const KERNEL: [[u32; 3]; 3] = [[1, 2, 1], [2, 4, 2], [1, 2, 1]];
+    let mut output = image.clone();
+    for y in 0..image.height {
+        for x in 0..image.width {
+            let (mut r, mut g, mut b) = (0, 0, 0);
+            for (dy, row) in KERNEL.iter().enumerate() {
+                for (dx, weight) in row.iter().enumerate() {
+                    let color =
+                        image.get(x as isize + dx as isize - 1, y as isize + dy as isize - 1);
+                    r += color.r as u32 * weight;
+                    g += color.g as u32 * weight;
+                    b += color.b as u32 * weight;
+                }
+            }
+            output.set(
+                x,
+                y,
+                Color::RGB((r / 16) as u8, (g / 16) as u8, (b / 16) as u8),
+            );
+        }
+    }
+    output
+}
+
+fn grid(image: &Image) -> Image {
+    let mut output = image.scaled(4);
+    for y in 0..output.height {
+        for x in 0..output.width {
+            if x % 4 == 3 || y % 4 == 3 {
+                let color = output.get(x as isize, y as isize);
+                output.set(x, y, darken(color, 3, 5));
+            }
+        }
+    }
+    output
+}
+
+fn scanlines(image: &Image) -> Image {
+    let mut output = image.scaled(2);
+    for y in (1..output.height).step_by(2) {
+        for x in 0..output.width {
+            let color = output.get(x as isize, y as isize);
+            output.set(x, y, darken(color, 1, 2));
+        }
+    }
+    output
+}
+
+fn crt(image: &Image) -> Image {
+    let mut output = image.scaled(3);
+    for y in 0..output.height {
+        for x in 0..output.width {
+            let color = output.get(x as isize, y as isize);
+            // each column favours one phosphor colour
+            let dim = |channel: u8, lit: bool| if lit { channel } else { channel / 3 * 2 };
+            let mut color = Color::RGB(
+                dim(color.r, x % 3 == 0),
+                dim(color.g, x % 3 == 1),
+                dim(color.b, x % 3 == 2),
+            );
+            if y % 3 == 2 {
+                color = darken(color, 1, 2);
+            }
+            output.set(x, y, color);
+        }
+    }
+    output
+}
+
+// scale a colour by numerator / denominator
+fn darken(color: Color, numerator: u32, denominator: u32) -> Color {
+    let scale = |channel: u8| (channel as u32 * numerator / denominator) as u8;
+    Color::RGB(scale(color.r), scale(color.g), scale(color.b))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const W: Color = Color::WHITE;
+    const B: Color = Color::BLACK;
+
+    fn image(width: usize, pixels: &[Color]) -> Image {
+        Image {
+            width,
+            height: pixels.len() / width,
+            pixels: pixels.to_vec(),
+        }
+    }
+
+    #[test]
+    fn parses_effect_lists() {
+        let pipeline = Pipeline::parse("epx, scanlines").unwrap();
+        assert_eq!(pipeline.effects, [Effect::Scale2x, Effect::Scanlines]);
+        assert_eq!(Pipeline::parse("none"), Some(Pipeline::default()));
+        assert_eq!(Pipeline::parse("scale2x,blur"), None);
+        for name in EFFECTS.iter() {
+            assert!(Pipeline::parse(name).is_some(), "{}", name);
+        }
+    }
+
+    #[test]
+    fn names_parse_back_to_the_same_pipeline() {
+        for text in ["none", "scale2x", "scale3x,smooth", "epx,crt"].iter() {
+            let pipeline = Pipeline::parse(text).unwrap();
+            assert_eq!(Pipeline::parse(&pipeline.name()), Some(pipeline));
+        }
+        assert_eq!(Pipeline::parse("epx,crt").unwrap().name(), "scale2x,crt");
+    }
+
+    #[test]
+    fn presets_cycle_back_to_the_plain_image() {
+        let mut pipeline = Pipeline::parse("crt").unwrap();
+        for _ in 0..PRESETS.len() {
+            pipeline = pipeline.next();
+        }
+        assert_eq!(pipeline.effects, PRESETS[PRESETS.len() - 1]);
+        assert_eq!(pipeline.next(), Pipeline::default());
+    }
+
+    #[test]
+    fn scale2x_fills_in_a_corner() {
+        // three white pixels around a black one
+        let output = scale2x(&image(2, &[W, W, W, B]));
+        assert_eq!((output.width, output.height), (4, 4));
+        #[rustfmt::skip]
+        let expected = [
+            W, W, W, W,
+            W, W, W, W,
+            W, W, W, B,
+            W, W, B, B,
+        ];
+        assert_eq!(output.pixels, expected);
+    }
+
+    #[test]
+    fn scalers_keep_flat_areas_flat() {
+        let flat = image(3, &[W; 9]);
+        assert!(scale2x(&flat).pixels.iter().all(|&c| c == W));
+        assert!(scale3x(&flat).pixels.iter().all(|&c| c == W));
+        assert!(smooth(&flat).pixels.iter().all(|&c| c == W));
+    }
+
+    #[test]
+    fn scanlines_darken_every_other_row() {
+        let output = Pipeline::parse("scanlines").unwrap().apply(image(1, &[W]));
+        assert_eq!(
+            output.pixels,
+            [W, W, Color::RGB(127, 127, 127), Color::RGB(127, 127, 127)]
+        );
+    }
+}
@@ -0,0 +1,88 @@
+use sdl2::keyboard::Keycode;
+
+// Emulator controls, kept apart from the keys passed to the rom
+#[derive(Clone)]
+pub struct Hotkeys {
+    pub quit: Keycode,
+    pub cycle_palette: Keycode,
+    pub cycle_filter: Keycode,
+    pub fullscreen: Keycode,
+    pub screenshot: Keycode,        // as shown, at the window's scale
+    pub screenshot_native: Keycode, // one pixel per chip 8 pixel
+    pub record: Keycode,            // start or stop a gif recording
+    pub mute: Keycode,
+    pub hud: Keycode,        // show or hide fps, ips, pc and keys
+    pub keypad: Keycode,     // show or hide the on-screen keypad
+    pub menu: Keycode,       // back to the launcher, when started from it
+    pub reset: Keycode,      // from the entry point, ram kept
+    pub hard_reset: Keycode, // as if the rom was just loaded
+    pub reload: Keycode,     // read the rom file again
+    pub pause: Keycode,
+    pub memory: Keycode,  // hex dump and editor, pauses while open
+    pub sprites: Keycode, // ram as sprites, pauses while open
+}
+
+impl Hotkeys {
+    // set a hotkey by its configuration name
+    pub fn set(&mut self, name: &str, keycode: Keycode) -> bool {
+        match name {
+            "quit" => self.quit = keycode,
+            "cycle_palette" => self.cycle_palette = keycode,
+            "cycle_filter" => self.cycle_filter = keycode,
+            "fullscreen" => self.fullscreen = keycode,
+            "screenshot" => self.screenshot = keycode,
+            "screenshot_native" => self.screenshot_native = keycode,
+            "record" => self.record = keycode,
+            "mute" => self.mute = keycode,
+            "hud" => self.hud = keycode,
+            "keypad" => self.keypad = keycode,
+            "menu" => self.menu = keycode,
+            "reset" => self.reset = keycode,
+            "hard_reset" => self.hard_reset = keycode,
+            "reload" => self.reload = keycode,
+            "pause" => self.pause = keycode,
+            "memory" => self.memory = keycode,
+            "sprites" => self.sprites = keycode,
+            _ => return false,
+        }
+        true
+    }
+}
+
+impl Default for Hotkeys {
+    fn default() -> Self {
+        Self {
+            quit: Keycode::Q,
+            cycle_palette: Keycode::P,
+            cycle_filter: Keycode::F3,
+            fullscreen: Keycode::F11,
+            screenshot: Keycode::F12,
+            screenshot_native: Keycode::F9,
+            record: Keycode::F7,
+            mute: Keycode::M,
+            hud: Keycode::F1,
+            keypad: Keycode::F2,
+            menu: Keycode::Escape,
+            reset: Keycode::F5,
+            hard_reset: Keycode::F6,
+            reload: Keycode::F8,
+            pause: Keycode::F4,
+            memory: Keycode::F10,
+            sprites: Keycode::I,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hotkeys_are_set_by_name() {
+        let mut hotkeys = Hotkeys::default();
+        assert_eq!(hotkeys.quit, Keycode::Q);
+        assert!(hotkeys.set("quit", Keycode::Escape));
+        assert_eq!(hotkeys.quit, Keycode::Escape);
+        assert!(!hotkeys.set("teleport", Keycode::P));
+    }
+}
@@ -0,0 +1,131 @@
+use sdl2::controller::Button;
+use sdl2::keyboard::Keycode;
+use std::collections::HashMap;
+
+// Host keys bound to the 16 chip 8 keys
+#[derive(Clone)]
+pub struct Keymap {
+    bindings: HashMap<Keycode, u8>,
+}
+
+impl Keymap {
+    pub fn get(&self, keycode: Keycode) -> Option<u8> {
+        self.bindings.get(&keycode).copied()
+    }
+
+    // host keys bound to a chip 8 key, by name
+    pub fn keycodes(&self, key: u8) -> Vec<Keycode> {
+        let mut keycodes: Vec<Keycode> = self
+            .bindings
+            .iter()
+            .filter(|(_, bound)| **bound == key)
+            .map(|(keycode, _)| *keycode)
+            .collect();
+        keycodes.sort_by_key(|keycode| keycode.name());
+        keycodes
+    }
+
+    pub fn bind(&mut self, keycode: Keycode, key: u8) {
+        self.bindings.insert(keycode, key);
+    }
+
+    // Bind a gamepad style name (as used in the CHIP-8 database) to a key
+    pub fn bind_named(&mut self, name: &str, key: u8) -> bool {
+        let keycode = match name {
+            "up" => Keycode::Up,
+            "down" => Keycode::Down,
+            "left" => Keycode::Left,
+            "right" => Keycode::Right,
+            "a" => Keycode::Space,
+            "b" => Keycode::Return,
+            _ => return false,
+        };
+        self.bind(keycode, key);
+        true
+    }
+}
+
+// the host key a gamepad button stands for, as in `bind_named`
+pub fn controller_keycode(button: Button) -> Option<Keycode> {
+    let keycode = match button {
+        Button::DPadUp => Keycode::Up,
+        Button::DPadDown => Keycode::Down,
+        Button::DPadLeft => Keycode::Left,
+        Button::DPadRight => Keycode::Right,
+        Button::A => Keycode::Space,
+        Button::B => Keycode::Return,
+        _ => return None,
+    };
+    Some(keycode)
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        let mut keymap = Self {
+            bindings: HashMap::new(),
+        };
+        let digits = [
+            Keycode::Num0,
+            Keycode::Num1,
+            Keycode::Num2,
+            Keycode::Num3,
+            Keycode::Num4,
+            Keycode::Num5,
+            Keycode::Num6,
+            Keycode::Num7,
+            Keycode::Num8,
+            Keycode::Num9,
+            Keycode::A,
+            Keycode::B,
+            Keycode::C,
+            Keycode::D,
+            Keycode::E,
+            Keycode::F,
+        ];
+        for (key, keycode) in digits.iter().enumerate() {
+            keymap.bind(*keycode, key as u8);
+        }
+        keymap.bind(Keycode::Left, 4);
+        keymap.bind(Keycode::Space, 5);
+        keymap.bind(Keycode::Right, 6);
+        keymap
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_keys_match_the_old_layout() {
+        let keymap = Keymap::default();
+        assert_eq!(keymap.get(Keycode::Num0), Some(0x0));
+        assert_eq!(keymap.get(Keycode::Num9), Some(0x9));
+        assert_eq!(keymap.get(Keycode::A), Some(0xA));
+        assert_eq!(keymap.get(Keycode::F), Some(0xF));
+        assert_eq!(keymap.get(Keycode::Left), Some(4));
+        assert_eq!(keymap.get(Keycode::Space), Some(5));
+        assert_eq!(keymap.get(Keycode::Right), Some(6));
+        assert_eq!(keymap.get(Keycode::Q), None);
+    }
+
+    #[test]
+    fn binding_a_host_key_again_moves_it() {
+        let mut keymap = Keymap::default();
+        keymap.bind(Keycode::Space, 0xB);
+        keymap.bind(Keycode::W, 2);
+        assert_eq!(keymap.get(Keycode::Space), Some(0xB));
+        assert_eq!(keymap.get(Keycode::W), Some(2));
+        assert_eq!(keymap.get(Keycode::Num5), Some(5));
+    }
+
+    #[test]
+    fn named_keys_bind_arrows_and_buttons() {
+        let mut keymap = Keymap::default();
+        assert!(keymap.bind_named("up", 3));
+        assert!(keymap.bind_named("a", 0xC));
+        assert!(!keymap.bind_named("start", 1));
+        assert_eq!(keymap.get(Keycode::Up), Some(3));
+        assert_eq!(keymap.get(Keycode::Space), Some(0xC));
+    }
+}
@@ -0,0 +1,140 @@
+use super::font::{self, GLYPH_HEIGHT};
+use super::keymap::Keymap;
+use crate::chip::KeyBoard;
+use sdl2::pixels::Color;
+use sdl2::rect::Rect;
+use sdl2::render::Canvas;
+use sdl2::video::Window;
+
+// COSMAC VIP keypad, row by row
+const LAYOUT: [u8; 16] = [
+    0x1, 0x2, 0x3, 0xC, //
+    0x4, 0x5, 0x6, 0xD, //
+    0x7, 0x8, 0x9, 0xE, //
+    0xA, 0x0, 0xB, 0xF,
+];
+
+const KEY: Color = Color::RGB(60, 60, 60);
+const PRESSED: Color = Color::RGB(230, 230, 230);
+const POLLED: Color = Color::RGB(255, 190, 0); // outline of keys the rom checks
+const LABEL: Color = Color::RGB(150, 150, 150);
+
+// The 16 keys drawn in a panel next to the game with the host keys
+// bound to them. Keys light up while down and are outlined while the
+// rom checks them, or all of them while it waits for one. Clicking a
+// key presses it.
+pub struct Keypad {
+    pub visible: bool,
+    pub clicked: Option<u8>,  // key held down with the mouse
+    labels: Vec<Vec<String>>, // host key names of each chip 8 key
+    pressed: u16,
+    polled: u16,
+    waiting: bool,
+}
+
+impl Keypad {
+    pub fn new(visible: bool, keymap: &Keymap) -> Self {
+        let labels = (0..16)
+            .map(|key| keymap.keycodes(key).iter().map(|k| k.name()).collect())
+            .collect();
+        Self {
+            visible,
+            clicked: None,
+            labels,
+            pressed: 0,
+            polled: 0,
+            waiting: false,
+        }
+    }
+
+    // once per frame, after the cpu ran
+    pub fn update(&mut self, keyboard: &KeyBoard) {
+        self.pressed = (0..16)
+            .filter(|key| keyboard.pressed_key[key])
+            .fold(0, |mask, key| mask | 1 << key);
+        self.polled = keyboard.polled;
+        self.waiting = keyboard.should_wait_for_key;
+    }
+
+    // the key under a point of the panel's area
+    pub fn key_at(area: Rect, x: i32, y: i32) -> Option<u8> {
+        (0..16)
+            .find(|i| cell(area, *i).contains_point((x, y)))
+            .map(|i| LAYOUT[i])
+    }
+
+    pub fn draw(&self, canvas: &mut Canvas<Window>, area: Rect) {
+        for (i, key) in LAYOUT.iter().enumerate() {
+            let rect = cell(area, i);
+            let bit = 1 << key;
+            if self.waiting || self.polled & bit != 0 {
+                canvas.set_draw_color(POLLED);
+                canvas.fill_rect(rect).unwrap();
+            }
+            let border = (rect.width() / 16).max(1);
+            let inner = Rect::new(
+                rect.x() + border as i32,
+                rect.y() + border as i32,
+                rect.width().saturating_sub(2 * border).max(1),
+                rect.height().saturating_sub(2 * border).max(1),
+            );
+            let pressed = self.pressed & bit != 0;
+            canvas.set_draw_color(if pressed { PRESSED } else { KEY });
+            canvas.fill_rect(inner).unwrap();
+
+            // the chip 8 key large, its host keys small under it
+            let size = (rect.height() / 12).max(1);
+            let digit = format!("{:X}", key);
+            canvas.set_draw_color(if pressed { KEY } else { PRESSED });
+            font::draw_text(
+                canvas,
+                size * 2,
+                inner.center().x() - font::text_width(&digit, size * 2) as i32 / 2,
+                inner.y() + size as i32,
+                &digit,
+            );
+            canvas.set_draw_color(LABEL);
+            let mut y = inner.y() + ((GLYPH_HEIGHT * 2 + 2) * size) as i32;
+            for label in self.labels[*key as usize].iter().take(2) {
+                let label = font::fit(label, inner.width(), size);
+                let x = inner.center().x() - font::text_width(&label, size) as i32 / 2;
+                font::draw_text(canvas, size, x, y, &label);
+                y += ((GLYPH_HEIGHT + 1) * size) as i32;
+            }
+        }
+    }
+}
+
+// square grid of 4x4 cells centred in the area
+fn cell(area: Rect, i: usize) -> Rect {
+    let side = area.width().min(area.height()) / 4;
+    let left = area.x() + ((area.width() - side * 4) / 2) as i32;
+    let top = area.y() + ((area.height() - side * 4) / 2) as i32;
+    let gap = (side / 12).max(1);
+    Rect::new(
+        left + ((i % 4) as u32 * side + gap) as i32,
+        top + ((i / 4) as u32 * side + gap) as i32,
+        side.saturating_sub(2 * gap).max(1),
+        side.saturating_sub(2 * gap).max(1),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn clicks_find_the_key_in_the_vip_layout() {
+        // 100 pixel cells with a gap of 8 around each key
+        let area = Rect::new(0, 0, 400, 400);
+        assert_eq!(Keypad::key_at(area, 50, 50), Some(0x1));
+        assert_eq!(Keypad::key_at(area, 350, 50), Some(0xC));
+        assert_eq!(Keypad::key_at(area, 150, 350), Some(0x0));
+        assert_eq!(Keypad::key_at(area, 350, 350), Some(0xF));
+        assert_eq!(Keypad::key_at(area, 100, 50), None);
+        // the grid is centred in a wider panel
+        let panel = Rect::new(100, 0, 600, 400);
+        assert_eq!(Keypad::key_at(panel, 50, 50), None);
+        assert_eq!(Keypad::key_at(panel, 250, 50), Some(0x1));
+    }
+}
@@ -0,0 +1,388 @@
+use super::font::{self, GLYPH_HEIGHT, GLYPH_WIDTH};
+use crate::chip::Cpu;
+use sdl2::keyboard::Keycode;
+use sdl2::pixels::Color;
+use sdl2::rect::Rect;
+use sdl2::render::Canvas;
+use sdl2::video::Window;
+
+const COLUMNS: usize = 16; // bytes per row
+const WRITE_FADE: u64 = 120; // frames a write stays highlighted
+
+pub const BACKGROUND: Color = Color::RGB(20, 20, 24);
+pub const TEXT: Color = Color::RGB(220, 220, 220);
+pub const DIM: Color = Color::RGB(130, 130, 140);
+const FONT: Color = Color::RGB(230, 200, 90); // the built in font's bytes
+const PC: Color = Color::RGB(40, 120, 60);
+pub const INDEX: Color = Color::RGB(50, 70, 140);
+const WRITE: (u8, u8, u8) = (150, 40, 40);
+
+enum Prompt {
+    None,
+    Goto(String),
+    Search(String), // hex bytes
+}
+
+// Hex dump of the whole ram over the window, with the program counter,
+// the sprite at I, recent writes and the font highlighted. The
+// emulation is paused while it is open, typing hex digits edits the
+// byte under the cursor.
+pub struct MemoryView {
+    pub open: bool,
+    cursor: usize,
+    top: usize,       // first row shown
+    rows: usize,      // rows that fit the window, from the last draw
+    high: Option<u8>, // first digit typed for the byte at the cursor
+    prompt: Prompt,
+    search: Vec<u8>,
+    status: Option<String>,
+}
+
+impl MemoryView {
+    pub fn new() -> Self {
+        Self {
+            open: false,
+            cursor: 0,
+            top: 0,
+            rows: 16,
+            high: None,
+            prompt: Prompt::None,
+            search: Vec::new(),
+            status: None,
+        }
+    }
+
+    // false when the key closes the view
+    pub fn key(&mut self, key: Keycode, cpu: &mut Cpu) -> bool {
+        if let Prompt::Goto(text) | Prompt::Search(text) = &mut self.prompt {
+            match key {
+                Keycode::Escape => self.prompt = Prompt::None,
+                Keycode::Return | Keycode::KpEnter => self.submit(cpu),
+                Keycode::Backspace => {
+                    text.pop();
+                }
+                Keycode::Space => text.push(' '),
+                _ => {
+                    if let Some(digit) = hex_digit(key) {
+                        text.push(std::char::from_digit(digit as u32, 16).unwrap());
+                    }
+                }
+            }
+            return true;
+        }
+
+        let size = cpu.ram().len() as isize;
+        let page = (self.rows * COLUMNS) as isize;
+        self.status = None;
+        match key {
+            Keycode::Escape => return false,
+            Keycode::Left => self.move_by(-1, size),
+            Keycode::Right => self.move_by(1, size),
+            Keycode::Up => self.move_by(-(COLUMNS as isize), size),
+            Keycode::Down => self.move_by(COLUMNS as isize, size),
+            Keycode::PageUp => self.move_by(-page, size),
+            Keycode::PageDown => self.move_by(page, size),
+            Keycode::Home => self.move_by(-size, size),
+            Keycode::End => self.move_by(size, size),
+            Keycode::G => self.prompt = Prompt::Goto(String::new()),
+            Keycode::Slash => self.prompt = Prompt::Search(String::new()),
+            Keycode::N => self.find_next(cpu),
+            _ => {
+                if let Some(digit) = hex_digit(key) {
+                    match self.high.take() {
+                        None => self.high = Some(digit),
+                        Some(high) => {
+                            cpu.poke(self.cursor, high << 4 | digit);
+                            self.move_by(1, size);
+                        }
+                    }
+                }
+            }
+        }
+        true
+    }
+
+    fn move_by(&mut self, delta: isize, size: isize) {
+        self.high = None;
+        self.cursor = (self.cursor as isize + delta).clamp(0, size - 1) as usize;
+    }
+
+    fn submit(&mut self, cpu: &Cpu) {
+        match std::mem::replace(&mut self.prompt, Prompt::None) {
+            Prompt::Goto(text) => match crate::parse_address(&text) {
+                Some(address) => {
+                    self.cursor = address;
+                    self.high = None;
+                }
+                None => self.status = Some(format!("invalid address `{}`", text)),
+            },
+            Prompt::Search(text) => match parse_bytes(&text) {
+                Some(bytes) => {
+                    self.search = bytes;
+                    self.find_next(cpu);
+                }
+                None => self.status = Some(format!("expected hex bytes, got `{}`", text)),
+            },
+            Prompt::None => {}
+        }
+    }
+
+    // the next match after the cursor, wrapping around
+    fn find_next(&mut self, cpu: &Cpu) {
+        if self.search.is_empty() {
+            self.status = Some("nothing to search, press / first".to_string());
+            return;
+        }
+        let ram = cpu.ram();
+        let found = (1..=ram.len())
+            .map(|offset| (self.cursor + offset) % ram.len())
+            .find(|&start| {
+                (0..self.search.len()).all(|i| ram.get(start + i) == Some(&self.search[i]))
+            });
+        match found {
+            Some(address) => {
+                self.cursor = address;
+                self.high = None;
+            }
+            None => self.status = Some("not found".to_string()),
+        }
+    }
+
+    pub fn draw(&mut self, canvas: &mut Canvas<Window>, cpu: &Cpu) {
+        let (_, height) = canvas.output_size().unwrap();
+        let size = (height / 200).max(1);
+        let line = (GLYPH_HEIGHT + 2) * size;
+        let advance = ((GLYPH_WIDTH + 1) * size) as i32;
+        let margin = line as i32;
+        canvas.set_draw_color(BACKGROUND);
+        canvas.clear();
+
+        let ram = cpu.ram();
+        canvas.set_draw_color(TEXT);
+        let stack = cpu.stack();
+        let entries: String = stack.iter().map(|a| format!(" {:03X}", a)).collect();
+        let header = format!(
+            "memory  pc {:03X}  i {:03X}  sp {}{}  cursor {:03X} = {:02X}",
+            cpu.pc(),
+            cpu.index(),
+            stack.len(),
+            entries,
+            self.cursor,
+            ram[self.cursor]
+        );
+        font::draw_text(canvas, size, margin, margin, &header);
+
+        // keep the cursor on screen
+        let top = margin + 2 * line as i32;
+        self.rows =
+            ((height as i32 - top - margin - 3 * line as i32) / line as i32).max(1) as usize;
+        let row = self.cursor / COLUMNS;
+        if row < self.top {
+            self.top = row;
+        } else if row >= self.top + self.rows {
+            self.top = row + 1 - self.rows;
+        }
+
+        let pc = cpu.pc() as usize;
+        let index = cpu.index() as usize;
+        let sprite = index..index + cpu.sprite_height() as usize;
+        let font_range = cpu.font_range();
+        let rows = ram.len() / COLUMNS;
+        for (i, row) in (self.top..rows).take(self.rows).enumerate() {
+            let y = top + i as i32 * line as i32;
+            canvas.set_draw_color(DIM);
+            font::draw_text(canvas, size, margin, y, &format!("{:03X}", row * COLUMNS));
+
+            for column in 0..COLUMNS {
+                let address = row * COLUMNS + column;
+                let byte = ram[address];
+                let x = margin + (5 + column as i32 * 3) * advance;
+                let ascii_x = margin + (5 + COLUMNS as i32 * 3 + 1 + column as i32) * advance;
+
+                let background = if address == self.cursor {
+                    Some(TEXT)
+                } else if address == pc || address == pc + 1 {
+                    Some(PC)
+                } else if sprite.contains(&address) {
+                    Some(INDEX)
+                } else {
+                    cpu.frames_since_write(address)
+                        .filter(|frames| *frames < WRITE_FADE)
+                        .map(|frames| fade(WRITE, frames))
+                };
+                if let Some(color) = background {
+                    canvas.set_draw_color(color);
+                    let cell =
+                        Rect::new(x - size as i32, y - size as i32, 3 * advance as u32, line);
+                    canvas.fill_rect(cell).unwrap();
+                }
+
+                let text = match self.high {
+                    Some(high) if address == self.cursor => format!("{:X}_", high),
+                    _ => format!("{:02X}", byte),
+                };
+                canvas.set_draw_color(if address == self.cursor {
+                    BACKGROUND
+                } else if font_range.contains(&address) {
+                    FONT
+                } else {
+                    TEXT
+                });
+                font::draw_text(canvas, size, x, y, &text);
+
+                let c = if (0x20..0x7F).contains(&byte) {
+                    byte as char
+                } else {
+                    '.'
+                };
+                canvas.set_draw_color(if address == self.cursor { TEXT } else { DIM });
+                font::draw_text(canvas, size, ascii_x, y, &c.to_string());
+            }
+        }
+
+        let footer = height as i32 - margin - line as i32;
+        let (color, text) = match (&self.prompt, &self.status) {
+            (Prompt::Goto(text), _) => (TEXT, format!("goto address: {}_", text)),
+            (Prompt::Search(text), _) => (TEXT, format!("search hex bytes: {}_", text)),
+            (Prompt::None, Some(status)) => (FONT, status.clone()),
+            (Prompt::None, None) => (
+                DIM,
+                "arrows move  hex digits edit  g goto  / search  n next  esc close".to_string(),
+            ),
+        };
+        canvas.set_draw_color(color);
+        font::draw_text(canvas, size, margin, footer, &text);
+        let legend = footer - line as i32;
+        for (i, (color, name)) in [
+            (PC, "pc"),
+            (INDEX, "sprite at i"),
+            (fade(WRITE, 0), "written"),
+        ]
+        .iter()
+        .enumerate()
+        {
+            let x = margin + i as i32 * 16 * advance;
+            canvas.set_draw_color(*color);
+            canvas
+                .fill_rect(Rect::new(x, legend, 3 * advance as u32, line - size))
+                .unwrap();
+            canvas.set_draw_color(DIM);
+            font::draw_text(canvas, size, x + 4 * advance, legend, name);
+        }
+        canvas.set_draw_color(FONT);
+        font::draw_text(canvas, size, margin + 48 * advance, legend, "font");
+    }
+}
+
+// a write's highlight, fading towards the background
+fn fade((r, g, b): (u8, u8, u8), frames: u64) -> Color {
+    let left = (WRITE_FADE - frames.min(WRITE_FADE)) as i32;
+    let mix =
+        |from: u8, to: u8| (to as i32 + (from as i32 - to as i32) * left / WRITE_FADE as i32) as u8;
+    Color::RGB(
+        mix(r, BACKGROUND.r),
+        mix(g, BACKGROUND.g),
+        mix(b, BACKGROUND.b),
+    )
+}
+
+fn hex_digit(key: Keycode) -> Option<u8> {
+    let name = key.name();
+    let mut chars = name.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) => c.to_digit(16).map(|digit| digit as u8),
+        _ => None,
+    }
+}
+
+// "A2 1E" or "a21e"
+fn parse_bytes(text: &str) -> Option<Vec<u8>> {
+    let digits: String = text.chars().filter(|c| !c.is_whitespace()).collect();
+    if digits.is_empty()
+        || !digits.len().is_multiple_of(2)
+        || !digits.chars().all(|c| c.is_ascii_hexdigit())
+    {
+        return None;
+    }
+    (0..digits.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&digits[i..i + 2], 16).ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chip::Rom;
+    use crate::options::Options;
+
+    fn cpu_with(program: &[u8]) -> Cpu {
+        let mut cpu = Cpu::new(&Options::default());
+        let rom = Rom {
+            data: program.to_vec(),
+            sha1: String::new(),
+        };
+        cpu.load(&rom).unwrap();
+        cpu
+    }
+
+    #[test]
+    fn search_text_is_hex_bytes() {
+        assert_eq!(parse_bytes("A2 1e"), Some(vec![0xA2, 0x1E]));
+        assert_eq!(parse_bytes("a21e"), Some(vec![0xA2, 0x1E]));
+        assert_eq!(parse_bytes("A2 1"), None);
+        assert_eq!(parse_bytes("zz"), None);
+        assert_eq!(parse_bytes(" "), None);
+    }
+
+    #[test]
+    fn find_next_wraps_around_the_ram() {
+        let cpu = cpu_with(&[0xA2, 0x1E, 0x00, 0xA2, 0x1E]);
+        let mut view = MemoryView::new();
+        view.prompt = Prompt::Search("a2 1e".to_string());
+        view.submit(&cpu);
+        assert_eq!(view.cursor, 0x200);
+        view.find_next(&cpu);
+        assert_eq!(view.cursor, 0x203);
+        view.find_next(&cpu);
+        assert_eq!(view.cursor, 0x200);
+
+        view.search = vec![0xDE, 0xAD];
+        view.find_next(&cpu);
+        assert_eq!(
+            (view.cursor, view.status.as_deref()),
+            (0x200, Some("not found"))
+        );
+    }
+
+    #[test]
+    fn goto_moves_the_cursor_or_reports_the_text() {
+        let cpu = cpu_with(&[]);
+        let mut view = MemoryView::new();
+        view.prompt = Prompt::Goto("3a0".to_string());
+        view.submit(&cpu);
+        assert_eq!(view.cursor, 0x3A0);
+        view.prompt = Prompt::Goto("fffff".to_string());
+        view.submit(&cpu);
+        assert_eq!(view.cursor, 0x3A0);
+        assert_eq!(view.status.as_deref(), Some("invalid address `fffff`"));
+    }
+
+    #[test]
+    fn pokes_are_remembered_as_writes() {
+        let mut cpu = cpu_with(&[0x12, 0x00]);
+        assert_eq!(cpu.frames_since_write(0x300), None);
+        cpu.poke(0x300, 0x42);
+        cpu.run_frame().unwrap();
+        cpu.run_frame().unwrap();
+        assert_eq!(cpu.ram()[0x300], 0x42);
+        assert_eq!(cpu.frames_since_write(0x300), Some(2));
+    }
+
+    #[test]
+    fn write_highlights_fade_into_the_background() {
+        assert_eq!(fade(WRITE, 0), Color::RGB(WRITE.0, WRITE.1, WRITE.2));
+        assert_eq!(fade(WRITE, WRITE_FADE), BACKGROUND);
+        assert_eq!(fade(WRITE, 10 * WRITE_FADE), BACKGROUND);
+    }
+}
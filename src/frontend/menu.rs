@@ -0,0 +1,174 @@
+use super::font::{self, GLYPH_HEIGHT, GLYPH_WIDTH};
+use super::screen::texture;
+use crate::image::Image;
+use crate::launcher::RomInfo;
+use crate::palette::Palette;
+use sdl2::pixels::Color;
+use sdl2::rect::Rect;
+use sdl2::render::{Canvas, TextureCreator};
+use sdl2::video::{Window, WindowContext};
+
+const BACKGROUND: Color = Color::RGB(20, 20, 24);
+const TEXT: Color = Color::RGB(220, 220, 220);
+const DIM: Color = Color::RGB(130, 130, 140);
+const SELECTED: Color = Color::RGB(60, 60, 110);
+const ERROR: Color = Color::RGB(255, 110, 90);
+
+// The launcher in the window: the roms of a directory on the left, the
+// details and preview of the selected one on the right
+pub struct Menu {
+    pub roms: Vec<RomInfo>,
+    pub selected: usize,
+    pub status: Option<String>, // why the last rom stopped, shown at the bottom
+    dir: String,
+    previews: Vec<Option<Image>>,
+}
+
+impl Menu {
+    pub fn new(dir: &str, roms: Vec<RomInfo>, palette: &Palette) -> Self {
+        let previews = roms.iter().map(|rom| rom.preview(palette)).collect();
+        Self {
+            roms,
+            selected: 0,
+            status: None,
+            dir: dir.to_string(),
+            previews,
+        }
+    }
+
+    // move the selection, stopping at either end
+    pub fn move_by(&mut self, delta: isize) {
+        let last = self.roms.len().saturating_sub(1) as isize;
+        self.selected = (self.selected as isize + delta).clamp(0, last) as usize;
+    }
+
+    pub fn draw(&self, canvas: &mut Canvas<Window>, creator: &TextureCreator<WindowContext>) {
+        let (width, height) = canvas.output_size().unwrap();
+        let size = (height / 200).max(1);
+        let line = (GLYPH_HEIGHT + 2) * size;
+        let margin = line;
+        canvas.set_draw_color(BACKGROUND);
+        canvas.clear();
+
+        canvas.set_draw_color(DIM);
+        let header = format!("roms in {}", self.dir);
+        font::draw_text(canvas, size, margin as i32, margin as i32, &header);
+
+        // the list, scrolled to keep the selection in the middle
+        let list_width = width * 2 / 5;
+        let top = margin + 2 * line;
+        let bottom = height.saturating_sub(margin + 2 * line);
+        let rows = (bottom.saturating_sub(top) / line).max(1) as usize;
+        let first = self
+            .selected
+            .saturating_sub(rows / 2)
+            .min(self.roms.len().saturating_sub(rows));
+        for (row, i) in (first..self.roms.len()).take(rows).enumerate() {
+            let y = (top + row as u32 * line) as i32;
+            if i == self.selected {
+                canvas.set_draw_color(SELECTED);
+                canvas
+                    .fill_rect(Rect::new(0, y - size as i32, list_width, line))
+                    .unwrap();
+            }
+            canvas.set_draw_color(TEXT);
+            let title = font::fit(
+                self.roms[i].title(),
+                list_width.saturating_sub(2 * margin),
+                size,
+            );
+            font::draw_text(canvas, size, margin as i32, y, &title);
+        }
+
+        // preview and details of the selection
+        let x = list_width + margin;
+        let column = width.saturating_sub(x + margin).max(1);
+        let mut y = top;
+        match self.previews.get(self.selected) {
+            Some(Some(image)) if image.width > 0 && image.height > 0 => {
+                let preview_height =
+                    (column * image.height as u32 / image.width as u32).min(height / 2);
+                let preview_width = preview_height * image.width as u32 / image.height as u32;
+                let texture = texture(creator, image);
+                let area = Rect::new(
+                    x as i32,
+                    y as i32,
+                    preview_width.max(1),
+                    preview_height.max(1),
+                );
+                canvas.copy(&texture, None, area).unwrap();
+                y += preview_height + line;
+            }
+            Some(_) => {
+                canvas.set_draw_color(DIM);
+                font::draw_text(canvas, size, x as i32, y as i32, "no preview");
+                y += 2 * line;
+            }
+            None => {}
+        }
+        if let Some(rom) = self.roms.get(self.selected) {
+            let chars = (column / ((GLYPH_WIDTH + 1) * size)).max(1) as usize;
+            for (i, paragraph) in rom.details().iter().enumerate() {
+                canvas.set_draw_color(if i == 0 { TEXT } else { DIM });
+                for text in wrap(paragraph, chars) {
+                    if y + line > bottom {
+                        break;
+                    }
+                    font::draw_text(canvas, size, x as i32, y as i32, &text);
+                    y += line;
+                }
+                y += line / 2;
+            }
+        }
+
+        let footer = height.saturating_sub(margin + line) as i32;
+        match &self.status {
+            Some(status) => {
+                canvas.set_draw_color(ERROR);
+                let status = font::fit(status, width.saturating_sub(2 * margin), size);
+                font::draw_text(canvas, size, margin as i32, footer, &status);
+            }
+            None => {
+                canvas.set_draw_color(DIM);
+                let help = "up/down select  enter run  esc quit  (esc in a game comes back here)";
+                font::draw_text(canvas, size, margin as i32, footer, help);
+            }
+        }
+    }
+}
+
+// words into lines of at most `chars` characters, long words are cut
+fn wrap(text: &str, chars: usize) -> Vec<String> {
+    let mut lines = Vec::new();
+    let mut line = String::new();
+    for word in text.split_whitespace() {
+        let length = line.chars().count();
+        if length > 0 && length + 1 + word.chars().count() > chars {
+            lines.push(std::mem::take(&mut line));
+        }
+        if !line.is_empty() {
+            line.push(' ');
+        }
+        line.push_str(word);
+        while let Some((at, _)) = line.char_indices().nth(chars) {
+            let rest = line.split_off(at);
+            lines.push(std::mem::replace(&mut line, rest));
+        }
+    }
+    if !line.is_empty() {
+        lines.push(line);
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wraps_words_and_cuts_long_ones() {
+        assert_eq!(wrap("a tiny  space game", 7), ["a tiny", "space", "game"]);
+        assert_eq!(wrap("kaleidoscope", 5), ["kalei", "dosco", "pe"]);
+        assert!(wrap("   ", 5).is_empty());
+    }
+}
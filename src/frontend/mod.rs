@@ -0,0 +1,478 @@
+mod font;
+mod hotkeys;
+mod keymap;
+mod keypad;
+mod memory;
+mod menu;
+mod osd;
+mod screen;
+mod sound;
+mod sprites;
+mod watch;
+
+use crate::audio::Audio;
+use crate::capture::{self, Recorder};
+use crate::chip::{Cpu, ExecError};
+use crate::options::Options;
+use crate::video::Video;
+pub use hotkeys::Hotkeys;
+pub use keymap::Keymap;
+use keypad::Keypad;
+use memory::MemoryView;
+pub use menu::Menu;
+use osd::Osd;
+use screen::Screen;
+use sdl2::controller::{Button, GameController};
+use sdl2::event::Event;
+use sdl2::keyboard::Keycode;
+use sdl2::mouse::MouseButton;
+use sound::Sound;
+use sprites::SpriteView;
+use std::path::PathBuf;
+use std::thread;
+use std::time::{Duration, Instant};
+use watch::FileWatch;
+
+// How a rom's run ended
+pub enum Exit {
+    Quit,
+    Menu,          // back to the launcher
+    HardReset,     // a new cpu with the same rom
+    Reload,        // a new cpu with the rom read from disk again
+    Open(PathBuf), // a file dropped on the window
+}
+
+// SDL window, audio and input around a cpu
+pub struct Frontend {
+    screen: Screen,
+    sound: Option<Audio<Sound>>, // None when no device opened or it failed later
+    muted: bool,
+    paused: bool,
+    osd: Osd,
+    memory: MemoryView,
+    sprites: SpriteView,
+    keypad: Keypad,
+    event_pump: sdl2::EventPump,
+    keymap: Keymap,
+    hotkeys: Hotkeys,
+    game_controller: Option<sdl2::GameControllerSubsystem>,
+    controllers: Vec<GameController>, // open while they are plugged in
+    launcher: bool,                   // the menu hotkey goes back to it
+    watch: Option<FileWatch>,         // rom file reloaded when it changes
+}
+
+impl Frontend {
+    pub fn new(options: Options) -> Self {
+        let sdl_context = sdl2::init().unwrap();
+        let event_pump = sdl_context.event_pump().unwrap();
+        let screen = Screen::new(&sdl_context, "Chip 8", 64, 32, &options);
+        let sound = match Sound::new(&sdl_context) {
+            Ok(sound) => Some(Audio::new(sound, &options)),
+            Err(e) => {
+                eprintln!("audio: {}, running without sound", e);
+                None
+            }
+        };
+        // controllers already plugged in are announced as added events
+        let game_controller = sdl_context.game_controller().ok();
+        Self {
+            screen,
+            sound,
+            muted: options.mute,
+            paused: false,
+            osd: Osd::new(options.hud),
+            memory: MemoryView::new(),
+            sprites: SpriteView::new(),
+            keypad: Keypad::new(options.keypad, &options.keymap),
+            event_pump,
+            keymap: options.keymap,
+            hotkeys: options.hotkeys,
+            game_controller,
+            controllers: Vec::new(),
+            launcher: false,
+            watch: None,
+        }
+    }
+
+    // settings of the next rom, keeping the window and the audio device
+    pub fn configure(&mut self, options: Options) {
+        if let Some(sound) = self.sound.take() {
+            self.sound = Some(Audio::new(sound.into_sink(), &options));
+        }
+        self.keypad = Keypad::new(self.keypad.visible, &options.keymap);
+        self.keymap = options.keymap;
+        self.hotkeys = options.hotkeys;
+    }
+
+    // Show the launcher until a rom is chosen, true, or the user quits.
+    // Arrows or the d-pad select, enter or A runs.
+    pub fn browse(&mut self, menu: &mut Menu) -> bool {
+        self.launcher = true;
+        let all = menu.roms.len() as isize;
+        loop {
+            let events: Vec<Event> = self.event_pump.poll_iter().collect();
+            for e in events {
+                match e {
+                    Event::Quit { .. } => return false,
+
+                    // a held key from the game must not quit at once
+                    Event::KeyDown {
+                        keycode: Some(key),
+                        repeat: false,
+                        ..
+                    } => match key {
+                        _ if key == self.hotkeys.quit || key == self.hotkeys.menu => return false,
+                        Keycode::Up => menu.move_by(-1),
+                        Keycode::Down => menu.move_by(1),
+                        Keycode::PageUp => menu.move_by(-10),
+                        Keycode::PageDown => menu.move_by(10),
+                        Keycode::Home => menu.move_by(-all),
+                        Keycode::End => menu.move_by(all),
+                        Keycode::Return | Keycode::KpEnter => return all > 0,
+                        _ => {}
+                    },
+
+                    Event::ControllerButtonDown { button, .. } => match button {
+                        Button::DPadUp => menu.move_by(-1),
+                        Button::DPadDown => menu.move_by(1),
+                        Button::LeftShoulder => menu.move_by(-10),
+                        Button::RightShoulder => menu.move_by(10),
+                        Button::A | Button::Start => return all > 0,
+                        Button::B | Button::Back => return false,
+                        _ => {}
+                    },
+
+                    Event::ControllerDeviceAdded { which, .. } => self.open_controller(which),
+                    _ => {}
+                }
+            }
+            self.screen
+                .draw(|canvas, creator| menu.draw(canvas, creator));
+            thread::sleep(Duration::from_micros(16666));
+        }
+    }
+
+    // reload the rom whenever this file is rewritten
+    pub fn watch(&mut self, path: &str) {
+        self.watch = Some(FileWatch::new(path));
+    }
+
+    // the sound of the last program stops with it
+    pub fn reset_audio(&mut self) {
+        if let Some(sound) = &mut self.sound {
+            sound.reset();
+        }
+    }
+
+    pub fn watching(&self) -> bool {
+        self.watch.is_some()
+    }
+
+    // shown over the game once it runs again
+    pub fn message(&mut self, text: String) {
+        self.osd.message(text);
+    }
+
+    fn open_controller(&mut self, which: u32) {
+        if let Some(subsystem) = &self.game_controller {
+            if let Ok(controller) = subsystem.open(which) {
+                self.controllers.push(controller);
+            }
+        }
+    }
+
+    // This will start execution of the program
+    pub fn run(
+        &mut self,
+        cpu: &mut Cpu,
+        video: &mut Video,
+        recorder: &mut Recorder,
+    ) -> Result<Exit, ExecError> {
+        let exit = 'main: loop {
+            let t0 = Instant::now();
+            // drain the queue, resizing the window queues many events
+            let events: Vec<Event> = self.event_pump.poll_iter().collect();
+            for e in events {
+                // an open view takes the keyboard, but keys let go of
+                // still reach the rom so none stays held once it closes
+                let release = matches!(
+                    e,
+                    Event::KeyUp { .. }
+                        | Event::MouseButtonUp { .. }
+                        | Event::ControllerButtonUp { .. }
+                );
+                if self.memory.open && !release {
+                    match e {
+                        Event::Quit { .. } => break 'main Exit::Quit,
+                        Event::KeyDown {
+                            keycode: Some(key), ..
+                        } if key == self.hotkeys.memory || !self.memory.key(key, cpu) => {
+                            self.memory.open = false
+                        }
+                        _ => {}
+                    }
+                    continue;
+                }
+                if self.sprites.open && !release {
+                    match e {
+                        Event::Quit { .. } => break 'main Exit::Quit,
+                        Event::KeyDown {
+                            keycode: Some(key), ..
+                        } if key == self.hotkeys.sprites
+                            || !self.sprites.key(key, cpu, &video.palette) =>
+                        {
+                            self.sprites.open = false
+                        }
+                        _ => {}
+                    }
+                    continue;
+                }
+
+                match e {
+                    Event::Quit { .. } => break 'main Exit::Quit,
+
+                    Event::KeyDown {
+                        keycode: Some(key), ..
+                    } if key == self.hotkeys.quit => break 'main Exit::Quit,
+
+                    Event::KeyDown {
+                        keycode: Some(key), ..
+                    } if key == self.hotkeys.pause => {
+                        self.paused = !self.paused;
+                        let text = if self.paused { "Paused" } else { "Resumed" };
+                        self.osd.message(text.to_string());
+                    }
+
+                    Event::KeyDown {
+                        keycode: Some(key), ..
+                    } if key == self.hotkeys.memory => self.memory.open = true,
+
+                    Event::KeyDown {
+                        keycode: Some(key), ..
+                    } if key == self.hotkeys.sprites => self.sprites.open = true,
+
+                    Event::KeyDown {
+                        keycode: Some(key), ..
+                    } if key == self.hotkeys.menu && self.launcher => break 'main Exit::Menu,
+
+                    Event::KeyDown {
+                        keycode: Some(key), ..
+                    } if key == self.hotkeys.reset => {
+                        cpu.soft_reset();
+                        video.clear();
+                        self.osd.message("Reset".to_string());
+                    }
+
+                    Event::KeyDown {
+                        keycode: Some(key), ..
+                    } if key == self.hotkeys.hard_reset => break 'main Exit::HardReset,
+
+                    Event::KeyDown {
+                        keycode: Some(key), ..
+                    } if key == self.hotkeys.reload => break 'main Exit::Reload,
+
+                    Event::DropFile { filename, .. } => break 'main Exit::Open(filename.into()),
+
+                    Event::KeyDown {
+                        keycode: Some(key), ..
+                    } if key == self.hotkeys.cycle_palette => {
+                        video.palette = video.palette.next();
+                        let name = video.palette.name.unwrap_or("custom");
+                        self.osd.message(format!("Palette {}", name));
+                    }
+
+                    Event::KeyDown {
+                        keycode: Some(key), ..
+                    } if key == self.hotkeys.cycle_filter => {
+                        video.pipeline = video.pipeline.next();
+                        self.osd
+                            .message(format!("Filter {}", video.pipeline.name()));
+                    }
+
+                    Event::KeyDown {
+                        keycode: Some(key), ..
+                    } if key == self.hotkeys.fullscreen => {
+                        self.screen.toggle_fullscreen();
+                    }
+
+                    Event::KeyDown {
+                        keycode: Some(key), ..
+                    } if key == self.hotkeys.screenshot
+                        || key == self.hotkeys.screenshot_native =>
+                    {
+                        let native = key == self.hotkeys.screenshot_native;
+                        let path = capture::timestamped("screenshot", "png");
+                        match capture::save_png(&video.capture(&cpu.display, native), &path) {
+                            Ok(()) => {
+                                eprintln!("saved {}", path.display());
+                                self.osd.message(format!("Saved {}", path.display()));
+                            }
+                            Err(e) => {
+                                eprintln!("{}", e);
+                                self.osd.message("Screenshot failed".to_string());
+                            }
+                        }
+                    }
+
+                    Event::KeyDown {
+                        keycode: Some(key), ..
+                    } if key == self.hotkeys.mute => {
+                        self.muted = !self.muted;
+                        let text = if self.muted { "Muted" } else { "Sound on" };
+                        self.osd.message(text.to_string());
+                    }
+
+                    Event::KeyDown {
+                        keycode: Some(key), ..
+                    } if key == self.hotkeys.record => {
+                        if recorder.recording_gif() {
+                            recorder.stop_gif();
+                            self.osd.message("Recording stopped".to_string());
+                        } else {
+                            let path = capture::timestamped("recording", "gif");
+                            eprintln!("recording {}", path.display());
+                            self.osd.message(format!("Recording {}", path.display()));
+                            recorder.start_gif(&path);
+                        }
+                    }
+
+                    Event::KeyDown {
+                        keycode: Some(key), ..
+                    } if key == self.hotkeys.hud => {
+                        self.osd.hud = !self.osd.hud;
+                    }
+
+                    Event::KeyDown {
+                        keycode: Some(key), ..
+                    } if key == self.hotkeys.keypad => {
+                        self.keypad.visible = !self.keypad.visible;
+                    }
+
+                    Event::KeyDown {
+                        keycode: Some(key), ..
+                    } => {
+                        if let Some(key) = self.keymap.get(key) {
+                            cpu.keyboard.on_key_down(key)
+                        } //ignore other keyboard char
+                    }
+
+                    Event::KeyUp {
+                        keycode: Some(key), ..
+                    } => {
+                        if let Some(key) = self.keymap.get(key) {
+                            cpu.keyboard.on_key_up(key)
+                        }
+                    }
+
+                    Event::MouseButtonDown {
+                        mouse_btn: MouseButton::Left,
+                        x,
+                        y,
+                        ..
+                    } => {
+                        if let Some(area) = self.screen.keypad_area(&self.keypad) {
+                            let (x, y) = self.screen.to_output(x, y);
+                            if let Some(key) = Keypad::key_at(area, x, y) {
+                                cpu.keyboard.on_key_down(key);
+                                self.keypad.clicked = Some(key);
+                            }
+                        }
+                    }
+
+                    Event::MouseButtonUp {
+                        mouse_btn: MouseButton::Left,
+                        ..
+                    } => {
+                        if let Some(key) = self.keypad.clicked.take() {
+                            cpu.keyboard.on_key_up(key);
+                        }
+                    }
+
+                    // gamepads stand in for the keys the rom database names
+                    Event::ControllerButtonDown {
+                        button: Button::Back,
+                        ..
+                    } if self.launcher => break 'main Exit::Menu,
+
+                    Event::ControllerButtonDown { button, .. } => {
+                        let key =
+                            keymap::controller_keycode(button).and_then(|k| self.keymap.get(k));
+                        if let Some(key) = key {
+                            cpu.keyboard.on_key_down(key)
+                        }
+                    }
+
+                    Event::ControllerButtonUp { button, .. } => {
+                        let key =
+                            keymap::controller_keycode(button).and_then(|k| self.keymap.get(k));
+                        if let Some(key) = key {
+                            cpu.keyboard.on_key_up(key)
+                        }
+                    }
+
+                    Event::ControllerDeviceAdded { which, .. } => self.open_controller(which),
+                    _ => {} // ignore other events
+                }
+            } // events matching end here
+
+            if self.watch.as_mut().is_some_and(|watch| watch.changed()) {
+                break 'main Exit::Reload;
+            }
+
+            // paused, emulated time stands still: no frame is run,
+            // recorded or played
+            let running = !self.paused && !self.memory.open && !self.sprites.open;
+            if running {
+                cpu.run_frame()?;
+
+                // update display after each  batch
+                video.update(cpu);
+                recorder.frame(video, cpu);
+            }
+            let (width, height) = video.resolution();
+            self.screen.set_resolution(width as u32, height as u32);
+            self.osd.update(cpu);
+            self.keypad.update(&cpu.keyboard);
+            if self.memory.open {
+                let memory = &mut self.memory;
+                self.screen.draw(|canvas, _| memory.draw(canvas, cpu));
+            } else if self.sprites.open {
+                let sprites = &mut self.sprites;
+                let palette = &video.palette;
+                self.screen
+                    .draw(|canvas, _| sprites.draw(canvas, cpu, palette));
+            } else {
+                self.screen.render(
+                    &video.image(),
+                    video.palette.color(0),
+                    &self.osd,
+                    &self.keypad,
+                );
+            }
+
+            // update sound, a paused tone fades out like a muted one
+            if let Some(sound) = &mut self.sound {
+                sound.set_muted(self.muted || !running);
+                let events = if running {
+                    cpu.sound_events()
+                } else {
+                    Vec::new()
+                };
+                if let Err(e) = sound.frame(&events) {
+                    eprintln!("audio: {}", e);
+                    self.osd.message("Audio stopped".to_string());
+                    self.sound = None;
+                }
+            }
+
+            //sleep for remaining microsecond of this iteration
+            let elapsed_time = t0.elapsed().as_micros() as u64;
+
+            // roughly 16666 micros in 1/60 seconds
+            thread::sleep(Duration::from_micros(
+                16666_u64.saturating_sub(elapsed_time),
+            ));
+        }; // main loop ends here
+        Ok(exit)
+    }
+}
@@ -0,0 +1,168 @@
+use super::font::{self, GLYPH_HEIGHT};
+use crate::chip::Cpu;
+use sdl2::pixels::Color;
+use sdl2::rect::Rect;
+use sdl2::render::{BlendMode, Canvas};
+use sdl2::video::Window;
+use std::collections::VecDeque;
+use std::time::Instant;
+
+const MESSAGE_FRAMES: u32 = 120; // two seconds
+const MAX_MESSAGES: usize = 4;
+
+// Text drawn over the game in window pixels, so it never shows up in
+// the display, screenshots or recordings: transient messages at the
+// bottom and an optional HUD at the top.
+pub struct Osd {
+    pub hud: bool,
+    messages: VecDeque<(String, u32)>, // text and frames left
+    hud_lines: Vec<String>,
+    since: Instant, // start of the current rate measurement
+    frames: u32,
+    cycles: u64,
+    fps: f32,
+    ips: u64, // instructions per second
+}
+
+impl Osd {
+    pub fn new(hud: bool) -> Self {
+        Self {
+            hud,
+            messages: VecDeque::new(),
+            hud_lines: Vec::new(),
+            since: Instant::now(),
+            frames: 0,
+            cycles: 0,
+            fps: 0.0,
+            ips: 0,
+        }
+    }
+
+    pub fn message(&mut self, text: String) {
+        if self.messages.len() == MAX_MESSAGES {
+            self.messages.pop_front();
+        }
+        self.messages.push_back((text, MESSAGE_FRAMES));
+    }
+
+    // once per frame, after the cpu ran
+    pub fn update(&mut self, cpu: &Cpu) {
+        for (_, frames) in &mut self.messages {
+            *frames -= 1;
+        }
+        self.messages.retain(|(_, frames)| *frames > 0);
+
+        self.frames += 1;
+        let elapsed = self.since.elapsed().as_secs_f32();
+        if elapsed >= 1.0 {
+            self.fps = self.frames as f32 / elapsed;
+            self.ips = ((cpu.cycles() - self.cycles) as f32 / elapsed) as u64;
+            self.since = Instant::now();
+            self.frames = 0;
+            self.cycles = cpu.cycles();
+        }
+
+        if self.hud {
+            let keys: Vec<String> = (0..16)
+                .filter(|key| cpu.keyboard.pressed_key[key])
+                .map(|key| format!("{:X}", key))
+                .collect();
+            let stack = cpu.stack();
+            let entries: String = stack.iter().map(|a| format!(" {:03X}", a)).collect();
+            self.hud_lines = vec![
+                format!("FPS {:.1}", self.fps),
+                format!("IPS {}", self.ips),
+                format!("PC {:03X}", cpu.pc()),
+                format!("SP {}{}", stack.len(), entries),
+                format!(
+                    "KEYS {}",
+                    if keys.is_empty() {
+                        "-".to_string()
+                    } else {
+                        keys.join(" ")
+                    }
+                ),
+            ];
+        }
+    }
+
+    pub fn draw(&self, canvas: &mut Canvas<Window>) {
+        let (width, height) = canvas.output_size().unwrap();
+        // font pixels grow with the window, 2 at the default 1024x512
+        let size = (width.min(height * 2) / 480).max(1);
+        let line = (GLYPH_HEIGHT + 2) * size;
+        canvas.set_blend_mode(BlendMode::Blend);
+
+        if self.hud {
+            for (i, text) in self.hud_lines.iter().enumerate() {
+                draw_text(
+                    canvas,
+                    size,
+                    size as i32,
+                    (size + i as u32 * line) as i32,
+                    text,
+                );
+            }
+        }
+        let count = self.messages.len() as u32;
+        for (i, (text, _)) in self.messages.iter().enumerate() {
+            let y = height as i32 - ((count - i as u32) * line + size) as i32;
+            draw_text(canvas, size, size as i32, y, text);
+        }
+    }
+}
+
+// white text on a translucent box, `size` window pixels per font pixel
+fn draw_text(canvas: &mut Canvas<Window>, size: u32, x: i32, y: i32, text: &str) {
+    let width = font::text_width(text, size) + 2 * size;
+    let height = (GLYPH_HEIGHT + 2) * size;
+    canvas.set_draw_color(Color::RGBA(0, 0, 0, 160));
+    canvas.fill_rect(Rect::new(x, y, width, height)).unwrap();
+    canvas.set_draw_color(Color::WHITE);
+    font::draw_text(canvas, size, x + size as i32, y + size as i32, text);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::options::Options;
+
+    fn texts(osd: &Osd) -> Vec<&str> {
+        osd.messages.iter().map(|(text, _)| text.as_str()).collect()
+    }
+
+    #[test]
+    fn messages_expire_after_two_seconds() {
+        let cpu = Cpu::new(&Options::default());
+        let mut osd = Osd::new(false);
+        osd.message("saved".to_string());
+        for _ in 0..MESSAGE_FRAMES - 1 {
+            osd.update(&cpu);
+        }
+        assert_eq!(texts(&osd), ["saved"]);
+        osd.update(&cpu);
+        assert!(texts(&osd).is_empty());
+    }
+
+    #[test]
+    fn only_the_newest_messages_are_kept() {
+        let mut osd = Osd::new(false);
+        for n in 0..MAX_MESSAGES + 1 {
+            osd.message(n.to_string());
+        }
+        assert_eq!(texts(&osd), ["1", "2", "3", "4"]);
+    }
+
+    #[test]
+    fn hud_shows_the_machine_state() {
+        let mut cpu = Cpu::new(&Options::default());
+        cpu.keyboard.on_key_down(0xA);
+        let mut osd = Osd::new(true);
+        osd.update(&cpu);
+        assert_eq!(osd.hud_lines[2..], ["PC 200", "SP 0", "KEYS A"]);
+        // nothing is measured without the hud
+        let mut osd = Osd::new(false);
+        osd.update(&cpu);
+        assert!(osd.hud_lines.is_empty());
+    }
+}
@@ -0,0 +1,222 @@
+use super::keypad::Keypad;
+use super::osd::Osd;
+use crate::image::Image;
+use crate::options::Options;
+use sdl2::pixels::{Color, PixelFormatEnum};
+use sdl2::rect::Rect;
+use sdl2::render::{Canvas, Texture, TextureCreator};
+use sdl2::surface::Surface;
+use sdl2::video::{FullscreenType, Window, WindowContext};
+
+// SDL window the framebuffer is presented in
+pub struct Screen {
+    renderer: Canvas<Window>,
+    texture_creator: TextureCreator<WindowContext>,
+    resolution: (u32, u32), // chip 8 pixels, 64x32 or 128x64 in hi-res
+    integer_scale: bool,    // only whole multiples of the resolution
+}
+
+impl Screen {
+    // window size, fullscreen, scaling and keypad come from the options
+    pub fn new(
+        sdl_context: &sdl2::Sdl,
+        title: &str,
+        width: u32,
+        height: u32,
+        options: &Options,
+    ) -> Self {
+        let video_subsystem = sdl_context.video().unwrap();
+        let scale = options.scale;
+        // room for a square keypad beside the game
+        let panel = if options.keypad { height * scale } else { 0 };
+        let mut window = video_subsystem
+            .window(title, width * scale + panel, height * scale)
+            .position_centered()
+            .resizable()
+            .build()
+            .unwrap();
+        if options.fullscreen {
+            window.set_fullscreen(FullscreenType::Desktop).unwrap();
+        }
+
+        let mut renderer = window.into_canvas().accelerated().build().unwrap();
+        let texture_creator = renderer.texture_creator();
+        renderer.clear();
+        Self {
+            renderer,
+            texture_creator,
+            resolution: (width, height),
+            integer_scale: options.integer_scale,
+        }
+    }
+
+    pub fn toggle_fullscreen(&mut self) {
+        let window = self.renderer.window_mut();
+        let state = match window.fullscreen_state() {
+            FullscreenType::Off => FullscreenType::Desktop,
+            _ => FullscreenType::Off,
+        };
+        window.set_fullscreen(state).unwrap();
+    }
+
+    // called every frame, the rom can switch resolution at any time
+    pub fn set_resolution(&mut self, width: u32, height: u32) {
+        self.resolution = (width, height);
+    }
+
+    // window areas of the game and of the keypad when it is shown
+    fn layout(&self, keypad: bool) -> (Rect, Option<Rect>) {
+        let (width, height) = self.renderer.output_size().unwrap();
+        if !keypad {
+            return (Rect::new(0, 0, width, height), None);
+        }
+        let (game, keypad) = split(width, height);
+        (game, Some(keypad))
+    }
+
+    pub fn keypad_area(&self, keypad: &Keypad) -> Option<Rect> {
+        self.layout(keypad.visible).1
+    }
+
+    // mouse position in renderer pixels, which differ from window
+    // coordinates on high dpi displays
+    pub fn to_output(&self, x: i32, y: i32) -> (i32, i32) {
+        let (window_width, window_height) = self.renderer.window().size();
+        let (width, height) = self.renderer.output_size().unwrap();
+        (
+            x * width as i32 / window_width.max(1) as i32,
+            y * height as i32 / window_height.max(1) as i32,
+        )
+    }
+
+    fn viewport(&self, area: Rect) -> Rect {
+        letterbox(area, self.resolution, self.integer_scale)
+    }
+
+    // the image is stretched over the viewport, so filters that upscale
+    // it add detail without changing the window, the osd goes on top
+    pub fn render(&mut self, image: &Image, background: Color, osd: &Osd, keypad: &Keypad) {
+        self.renderer.set_draw_color(background);
+        self.renderer.clear();
+
+        let texture = texture(&self.texture_creator, image);
+        let (game, panel) = self.layout(keypad.visible);
+        let viewport = self.viewport(game);
+        self.renderer.copy(&texture, None, viewport).unwrap();
+        if let Some(panel) = panel {
+            keypad.draw(&mut self.renderer, panel);
+        }
+        osd.draw(&mut self.renderer);
+        self.renderer.present();
+    }
+
+    // a whole frame drawn by someone else, like the launcher
+    pub fn draw(&mut self, draw: impl FnOnce(&mut Canvas<Window>, &TextureCreator<WindowContext>)) {
+        draw(&mut self.renderer, &self.texture_creator);
+        self.renderer.present();
+    }
+}
+
+pub fn texture<'a>(creator: &'a TextureCreator<WindowContext>, image: &Image) -> Texture<'a> {
+    let mut pixels = image.to_argb();
+    let surface = Surface::from_data(
+        pixels.as_mut(),
+        image.width as u32,
+        image.height as u32,
+        image.width as u32 * 4,
+        PixelFormatEnum::ARGB8888,
+    )
+    .unwrap();
+    creator.create_texture_from_surface(surface).unwrap()
+}
+
+// game and keypad areas of a window: the keypad goes beside the game
+// in wide windows and under it in tall ones
+fn split(width: u32, height: u32) -> (Rect, Rect) {
+    if width >= height {
+        let side = height.min(width / 3);
+        let game = Rect::new(0, 0, width - side, height);
+        (game, Rect::new((width - side) as i32, 0, side, height))
+    } else {
+        let side = width.min(height / 2);
+        let game = Rect::new(0, 0, width, height - side);
+        (game, Rect::new(0, (height - side) as i32, width, side))
+    }
+}
+
+// largest area with the resolution's aspect ratio that fits in `area`,
+// centred with bars on the sides that do not fit
+fn letterbox(area: Rect, resolution: (u32, u32), integer_scale: bool) -> Rect {
+    let (area_width, area_height) = (area.width(), area.height());
+    let (width, height) = resolution;
+    let mut scale = (area_width as f32 / width as f32).min(area_height as f32 / height as f32);
+    if integer_scale && scale >= 1.0 {
+        scale = scale.floor();
+    }
+    let (view_width, view_height) = (
+        (width as f32 * scale) as u32,
+        (height as f32 * scale) as u32,
+    );
+    Rect::new(
+        area.x() + ((area_width - view_width) / 2) as i32,
+        area.y() + ((area_height - view_height) / 2) as i32,
+        view_width.max(1),
+        view_height.max(1),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keypad_goes_beside_or_under_the_game() {
+        assert_eq!(
+            split(1200, 500),
+            (Rect::new(0, 0, 800, 500), Rect::new(800, 0, 400, 500))
+        );
+        assert_eq!(
+            split(900, 600),
+            (Rect::new(0, 0, 600, 600), Rect::new(600, 0, 300, 600))
+        );
+        assert_eq!(
+            split(400, 1000),
+            (Rect::new(0, 0, 400, 600), Rect::new(0, 600, 400, 400))
+        );
+    }
+
+    #[test]
+    fn letterbox_keeps_the_aspect_ratio() {
+        let window = Rect::new(0, 0, 800, 600);
+        // bars above and below
+        assert_eq!(
+            letterbox(window, (64, 32), false),
+            Rect::new(0, 100, 800, 400)
+        );
+        // bars on the sides
+        let tall = Rect::new(0, 0, 1000, 200);
+        assert_eq!(
+            letterbox(tall, (64, 32), false),
+            Rect::new(300, 0, 400, 200)
+        );
+    }
+
+    #[test]
+    fn integer_scale_uses_whole_multiples() {
+        let window = Rect::new(0, 0, 800, 600);
+        // 12.5x rounds down to 12x
+        assert_eq!(
+            letterbox(window, (64, 32), true),
+            Rect::new(16, 108, 768, 384)
+        );
+        // windows smaller than the resolution still shrink it
+        let tiny = Rect::new(0, 0, 32, 32);
+        assert_eq!(letterbox(tiny, (64, 32), true), Rect::new(0, 8, 32, 16));
+    }
+
+    #[test]
+    fn letterbox_stays_inside_an_offset_area() {
+        let area = Rect::new(10, 20, 128, 128);
+        assert_eq!(letterbox(area, (64, 32), false), Rect::new(10, 52, 128, 64));
+    }
+}
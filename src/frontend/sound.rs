@@ -0,0 +1,45 @@
+use crate::audio::SampleSink;
+use sdl2::audio::{AudioQueue, AudioSpecDesired};
+use sdl2::Sdl;
+
+// Frames of audio allowed to wait in the queue. When the emulation runs
+// ahead of the sound card, frames beyond this are dropped to keep the
+// latency down.
+const MAX_QUEUED_FRAMES: u32 = 4;
+
+// Samples queued to the sound card as the emulation produces them
+pub struct Sound {
+    queue: AudioQueue<f32>,
+}
+
+impl Sound {
+    // fails on hosts without a sound card, like CI or ssh sessions
+    pub fn new(sdl_context: &Sdl) -> Result<Self, String> {
+        let audio_subsystem = sdl_context.audio()?;
+        let spec_desired = AudioSpecDesired {
+            freq: Some(44100),
+            channels: Some(1),
+            samples: None,
+        };
+        let queue = audio_subsystem.open_queue::<f32, _>(None, &spec_desired)?;
+        queue.resume();
+        Ok(Self { queue })
+    }
+}
+
+impl SampleSink for Sound {
+    fn sample_rate(&self) -> u32 {
+        self.queue.spec().freq as u32
+    }
+
+    fn write(&mut self, samples: &[f32]) -> Result<(), String> {
+        let queued = self.queue.size() / std::mem::size_of::<f32>() as u32;
+        if queued > self.sample_rate() / 60 * MAX_QUEUED_FRAMES {
+            return Ok(());
+        }
+        if !self.queue.queue(samples) {
+            return Err(sdl2::get_error());
+        }
+        Ok(())
+    }
+}
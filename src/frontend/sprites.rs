@@ -0,0 +1,336 @@
+use super::font::{self, GLYPH_HEIGHT, GLYPH_WIDTH};
+use super::memory::{BACKGROUND, DIM, INDEX, TEXT};
+use crate::capture;
+use crate::chip::Cpu;
+use crate::image::Image;
+use crate::palette::Palette;
+use sdl2::keyboard::Keycode;
+use sdl2::pixels::Color;
+use sdl2::rect::Rect;
+use sdl2::render::Canvas;
+use sdl2::video::Window;
+use std::path::PathBuf;
+
+const COLUMN_ROWS: usize = 16; // sprite rows in each column of the region
+const SELECTED: Color = Color::RGB(60, 60, 70);
+
+// Ram drawn as sprite rows, 8 pixels a byte or 16x16 like SCHIP's
+// Dx00, next to every address the rom drew a sprite from so far. It
+// follows I until moved, and pauses the emulation while open.
+pub struct SpriteView {
+    pub open: bool,
+    follow: bool, // start at I on every frame
+    address: usize,
+    wide: bool,      // two bytes a row
+    selected: usize, // in the drawn sprites list
+    pages: usize,    // rows of the region shown, from the last draw
+    goto: Option<String>,
+    status: Option<String>,
+}
+
+impl SpriteView {
+    pub fn new() -> Self {
+        Self {
+            open: false,
+            follow: true,
+            address: 0,
+            wide: false,
+            selected: 0,
+            pages: COLUMN_ROWS,
+            goto: None,
+            status: None,
+        }
+    }
+
+    // false when the key closes the view
+    pub fn key(&mut self, key: Keycode, cpu: &Cpu, palette: &Palette) -> bool {
+        if let Some(text) = &mut self.goto {
+            match key {
+                Keycode::Escape => self.goto = None,
+                Keycode::Return | Keycode::KpEnter => {
+                    match crate::parse_address(text) {
+                        Some(address) => self.jump(address, cpu),
+                        None => self.status = Some(format!("invalid address `{}`", text)),
+                    }
+                    self.goto = None;
+                }
+                Keycode::Backspace => {
+                    text.pop();
+                }
+                _ => {
+                    let name = key.name();
+                    if name.len() == 1 && name.chars().all(|c| c.is_ascii_hexdigit()) {
+                        text.push_str(&name.to_lowercase());
+                    }
+                }
+            }
+            return true;
+        }
+
+        let row = if self.wide { 2 } else { 1 };
+        let sources: Vec<u16> = cpu.sprite_sources().keys().copied().collect();
+        self.status = None;
+        match key {
+            Keycode::Escape => return false,
+            Keycode::Up => self.scroll(-row, cpu),
+            Keycode::Down => self.scroll(row, cpu),
+            Keycode::PageUp => self.scroll(-(self.pages as isize) * row, cpu),
+            Keycode::PageDown => self.scroll(self.pages as isize * row, cpu),
+            Keycode::Left | Keycode::Right if !sources.is_empty() => {
+                self.selected = if key == Keycode::Left {
+                    (self.selected + sources.len() - 1) % sources.len()
+                } else {
+                    (self.selected + 1) % sources.len()
+                };
+                self.jump(sources[self.selected] as usize, cpu);
+            }
+            Keycode::F => {
+                self.follow = !self.follow;
+                if self.follow {
+                    self.address = cpu.index() as usize;
+                }
+            }
+            Keycode::W => self.wide = !self.wide,
+            Keycode::G => self.goto = Some(String::new()),
+            Keycode::E => {
+                self.status = Some(match export(cpu, palette) {
+                    Ok((dir, count)) => format!("{} sprites saved to {}", count, dir.display()),
+                    Err(e) => e,
+                })
+            }
+            _ => {}
+        }
+        true
+    }
+
+    fn jump(&mut self, address: usize, cpu: &Cpu) {
+        self.follow = false;
+        self.address = address % cpu.ram().len();
+    }
+
+    fn scroll(&mut self, delta: isize, cpu: &Cpu) {
+        let size = cpu.ram().len() as isize;
+        self.jump(
+            (self.address as isize + delta).rem_euclid(size) as usize,
+            cpu,
+        );
+    }
+
+    pub fn draw(&mut self, canvas: &mut Canvas<Window>, cpu: &Cpu, palette: &Palette) {
+        let (width, height) = canvas.output_size().unwrap();
+        let size = (height / 200).max(1);
+        let line = (GLYPH_HEIGHT + 2) * size;
+        let advance = ((GLYPH_WIDTH + 1) * size) as i32;
+        let margin = line as i32;
+        canvas.set_draw_color(BACKGROUND);
+        canvas.clear();
+
+        if self.follow {
+            self.address = cpu.index() as usize;
+        }
+        let ram = cpu.ram();
+        let sources = cpu.sprite_sources();
+        canvas.set_draw_color(TEXT);
+        let header = format!(
+            "sprites at {:03X}  {}  i {:03X}{}",
+            self.address,
+            if self.wide { "16x16" } else { "8 wide" },
+            cpu.index(),
+            if self.follow { "  following i" } else { "" }
+        );
+        font::draw_text(canvas, size, margin, margin, &header);
+
+        // the region, in columns of COLUMN_ROWS rows with a pixel scale
+        // that fits them under the header
+        let top = margin + 2 * line as i32;
+        let bottom = height as i32 - margin - 2 * line as i32;
+        let bytes = if self.wide { 2 } else { 1 };
+        let scale = ((bottom - top) / COLUMN_ROWS as i32).max(1);
+        let label = 4 * advance;
+        let column_width = label + (8 * bytes) as i32 * scale + margin;
+        let region_width = width as i32 * 3 / 5 - margin;
+        let columns = (region_width / column_width).max(1) as usize;
+        self.pages = columns * COLUMN_ROWS;
+
+        let index = cpu.index() as usize;
+        let sprite = index..index + cpu.sprite_height() as usize;
+        for column in 0..columns {
+            let x = margin + column as i32 * column_width;
+            for row in 0..COLUMN_ROWS {
+                let y = top + row as i32 * scale;
+                let address = (self.address + (column * COLUMN_ROWS + row) * bytes) % ram.len();
+                if row % 4 == 0 {
+                    canvas.set_draw_color(DIM);
+                    font::draw_text(canvas, size, x, y, &format!("{:03X}", address));
+                }
+                let area = Rect::new(x + label, y, 8 * bytes as u32 * scale as u32, scale as u32);
+                if (0..bytes).any(|byte| sprite.contains(&(address + byte))) {
+                    canvas.set_draw_color(INDEX);
+                    canvas
+                        .fill_rect(Rect::new(
+                            area.x() - size as i32,
+                            area.y(),
+                            area.width() + 2 * size,
+                            area.height(),
+                        ))
+                        .unwrap();
+                }
+                let pixels: Vec<u8> = (0..bytes).map(|i| ram[(address + i) % ram.len()]).collect();
+                draw_row(canvas, palette, area, &pixels, scale as u32);
+            }
+        }
+
+        // every address drawn from, with the sprite's rows next to it,
+        // when a narrow window leaves room for it
+        let list_x = margin + region_width + margin;
+        let list_width = (width as i32 - list_x).max(0) as u32;
+        if list_width > 0 {
+            canvas.set_draw_color(TEXT);
+            font::draw_text(
+                canvas,
+                size,
+                list_x,
+                top - line as i32,
+                &format!("drawn from ({})", sources.len()),
+            );
+            // n is at most 15, so a sprite always fits an entry at text scale
+            let entry = 16 * size;
+            let shown = ((bottom - top) / entry as i32).max(1) as usize;
+            self.selected = self.selected.min(sources.len().saturating_sub(1));
+            let first = (self.selected + 1).saturating_sub(shown);
+            for (i, (address, rows)) in sources.iter().enumerate().skip(first).take(shown) {
+                let y = top + (i - first) as i32 * entry as i32;
+                if i == self.selected {
+                    canvas.set_draw_color(SELECTED);
+                    canvas
+                        .fill_rect(Rect::new(list_x - size as i32, y, list_width, entry))
+                        .unwrap();
+                }
+                canvas.set_draw_color(if *address as usize == self.address {
+                    TEXT
+                } else {
+                    DIM
+                });
+                font::draw_text(
+                    canvas,
+                    size,
+                    list_x,
+                    y + size as i32,
+                    &format!("{:03X} 8x{:<2}", address, rows),
+                );
+                let x = list_x + 9 * advance;
+                for row in 0..*rows as u32 {
+                    let area = Rect::new(x, y + (row * size) as i32, 8 * size, size);
+                    let byte = ram[(*address as usize + row as usize) % ram.len()];
+                    draw_row(canvas, palette, area, &[byte], size);
+                }
+            }
+        }
+
+        let footer = height as i32 - margin - line as i32;
+        let (color, text) = match (&self.goto, &self.status) {
+            (Some(text), _) => (TEXT, format!("goto address: {}_", text)),
+            (None, Some(status)) => (TEXT, status.clone()),
+            (None, None) => (
+                DIM,
+                "up down scroll  left right drawn sprites  f follow i  w width  g goto  e export  esc close"
+                    .to_string(),
+            ),
+        };
+        canvas.set_draw_color(color);
+        let text = font::fit(&text, width.saturating_sub(2 * margin as u32), size);
+        font::draw_text(canvas, size, margin, footer, &text);
+    }
+}
+
+// one row of a sprite, most significant bit first, in the palette's
+// first two colours
+fn draw_row(canvas: &mut Canvas<Window>, palette: &Palette, area: Rect, bytes: &[u8], scale: u32) {
+    canvas.set_draw_color(palette.color(0));
+    canvas.fill_rect(area).unwrap();
+    canvas.set_draw_color(palette.color(1));
+    for (i, byte) in bytes.iter().enumerate() {
+        for bit in 0..8 {
+            if byte & (0x80 >> bit) != 0 {
+                let x = area.x() + ((i * 8 + bit) as u32 * scale) as i32;
+                canvas
+                    .fill_rect(Rect::new(x, area.y(), scale, scale))
+                    .unwrap();
+            }
+        }
+    }
+}
+
+// every sprite drawn so far as its own PNG, one pixel a bit, named by
+// address and size in a new `sprites-<timestamp>` directory
+fn export(cpu: &Cpu, palette: &Palette) -> Result<(PathBuf, usize), String> {
+    let sources = cpu.sprite_sources();
+    if sources.is_empty() {
+        return Err("no sprite drawn yet".to_string());
+    }
+    let dir = PathBuf::from(format!("sprites-{}", capture::timestamp()));
+    std::fs::create_dir(&dir).map_err(|e| format!("{}: {}", dir.display(), e))?;
+    let ram = cpu.ram();
+    for (address, rows) in sources {
+        let mut image = Image::new(8, *rows as usize, palette.color(0));
+        for row in 0..*rows as usize {
+            let byte = ram[(*address as usize + row) % ram.len()];
+            for bit in 0..8 {
+                if byte & (0x80 >> bit) != 0 {
+                    image.set(bit, row, palette.color(1));
+                }
+            }
+        }
+        let path = dir.join(format!("{:03X}-8x{}.png", address, rows));
+        capture::save_png(&image, &path)?;
+    }
+    Ok((dir, sources.len()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chip::Rom;
+    use crate::options::Options;
+
+    // draws from 0x208 and 0x20A, then stops with I at 0x20A
+    fn cpu() -> Cpu {
+        let mut cpu = Cpu::new(&Options::default());
+        let rom = Rom {
+            data: vec![
+                0xA2, 0x08, 0xD0, 0x11, 0xA2, 0x0A, 0xD0, 0x12, 0x12, 0x08, 0xFF, 0x81,
+            ],
+            sha1: String::new(),
+        };
+        cpu.load(&rom).unwrap();
+        cpu.run_frame().unwrap();
+        cpu.run_frame().unwrap();
+        cpu
+    }
+
+    #[test]
+    fn left_and_right_step_through_the_drawn_sprites() {
+        let (cpu, palette) = (cpu(), Palette::default());
+        let mut view = SpriteView::new();
+        assert!(view.key(Keycode::Right, &cpu, &palette));
+        assert_eq!((view.address, view.follow), (0x20A, false));
+        view.key(Keycode::Right, &cpu, &palette);
+        assert_eq!(view.address, 0x208);
+        view.key(Keycode::Left, &cpu, &palette);
+        assert_eq!(view.address, 0x20A);
+        assert!(!view.key(Keycode::Escape, &cpu, &palette));
+    }
+
+    #[test]
+    fn scrolling_stops_following_i_and_wraps() {
+        let (cpu, palette) = (cpu(), Palette::default());
+        let mut view = SpriteView::new();
+        // nothing drawn yet, so the view is still at 0
+        view.key(Keycode::W, &cpu, &palette);
+        view.key(Keycode::Up, &cpu, &palette);
+        assert_eq!(view.address, cpu.ram().len() - 2);
+        assert!(!view.follow);
+        view.key(Keycode::F, &cpu, &palette);
+        assert_eq!((view.address, view.follow), (0x20A, true));
+    }
+}
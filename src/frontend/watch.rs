@@ -0,0 +1,87 @@
+use std::fs;
+use std::path::PathBuf;
+use std::time::{Duration, Instant, SystemTime};
+
+const POLL: Duration = Duration::from_millis(250);
+
+// Polls a file's modification time. A change is reported once the time
+// stayed the same for one more poll, so a file an assembler is still
+// writing is not read half way. A missing file is waited for.
+pub struct FileWatch {
+    path: PathBuf,
+    modified: Option<SystemTime>,
+    pending: Option<SystemTime>, // new time seen on the last poll
+    polled: Instant,
+}
+
+impl FileWatch {
+    pub fn new(path: &str) -> Self {
+        let path = PathBuf::from(path);
+        Self {
+            modified: modified(&path),
+            path,
+            pending: None,
+            polled: Instant::now(),
+        }
+    }
+
+    // cheap to call every frame, the file is looked at every POLL
+    pub fn changed(&mut self) -> bool {
+        if self.polled.elapsed() < POLL {
+            return false;
+        }
+        self.polled = Instant::now();
+        let now = modified(&self.path);
+        self.settled(now)
+    }
+
+    // whether a modification time seen on a poll is a finished change
+    fn settled(&mut self, now: Option<SystemTime>) -> bool {
+        if now.is_none() || now == self.modified {
+            self.pending = None;
+            return false;
+        }
+        if now != self.pending {
+            self.pending = now;
+            return false;
+        }
+        self.modified = now;
+        self.pending = None;
+        true
+    }
+}
+
+fn modified(path: &PathBuf) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_change_is_reported_once_the_file_settles() {
+        let start = SystemTime::UNIX_EPOCH;
+        let time = |seconds| Some(start + Duration::from_secs(seconds));
+        let mut watch = FileWatch::new("no-such-rom.ch8");
+        watch.modified = time(0);
+
+        assert!(!watch.settled(time(0)));
+        // still being written
+        assert!(!watch.settled(time(1)));
+        assert!(!watch.settled(time(2)));
+        assert!(watch.settled(time(2)));
+        // reported once
+        assert!(!watch.settled(time(2)));
+    }
+
+    #[test]
+    fn a_missing_file_is_waited_for() {
+        let mut watch = FileWatch::new("no-such-rom.ch8");
+        assert_eq!(watch.modified, None);
+        assert!(!watch.settled(None));
+        let written = Some(SystemTime::UNIX_EPOCH);
+        assert!(!watch.settled(written));
+        assert!(watch.settled(written));
+    }
+}
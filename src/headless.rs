@@ -0,0 +1,11 @@
+use crate::chip::{Cpu, ExecError};
+
+// Run a number of frames as fast as possible, without window or audio.
+// `on_frame` sees the cpu after every frame.
+pub fn run(cpu: &mut Cpu, frames: u64, mut on_frame: impl FnMut(&Cpu)) -> Result<(), ExecError> {
+    for _ in 0..frames {
+        cpu.run_frame()?;
+        on_frame(cpu);
+    }
+    Ok(())
+}
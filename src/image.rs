@@ -0,0 +1,103 @@
+use crate::chip::Display;
+use crate::palette::Palette;
+use crate::persistence::Frame;
+use sdl2::pixels::Color;
+
+// Coloured picture of the screen, what the window, screenshots and
+// recordings are made from
+#[derive(Clone, PartialEq)]
+pub struct Image {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<Color>, // row by row
+}
+
+impl Image {
+    pub fn new(width: usize, height: usize, fill: Color) -> Self {
+        Self {
+            width,
+            height,
+            pixels: vec![fill; width * height],
+        }
+    }
+
+    pub fn from_frame(frame: &Frame, palette: &Palette) -> Self {
+        let pixels = frame
+            .pixels
+            .iter()
+            .zip(&frame.levels)
+            .map(|(pixel, level)| palette.blend(*pixel, *level))
+            .collect();
+        Self {
+            width: frame.width,
+            height: frame.height,
+            pixels,
+        }
+    }
+
+    pub fn from_display(display: &Display, palette: &Palette) -> Self {
+        Self {
+            width: display.width(),
+            height: display.height(),
+            pixels: display.buffer.iter().map(|p| palette.color(*p)).collect(),
+        }
+    }
+
+    // nearest neighbour, each pixel becomes a factor x factor block
+    pub fn scaled(&self, factor: usize) -> Image {
+        let mut output = Image::new(self.width * factor, self.height * factor, Color::BLACK);
+        for y in 0..output.height {
+            for x in 0..output.width {
+                let color = self.pixels[y / factor * self.width + x / factor];
+                output.set(x, y, color);
+            }
+        }
+        output
+    }
+
+    // nearest neighbour to any size, for recordings that keep the size
+    // they started with when the filter or scale changes
+    pub fn resized(&self, width: usize, height: usize) -> Image {
+        let mut output = Image::new(width, height, Color::BLACK);
+        for y in 0..height {
+            for x in 0..width {
+                let color = self.get(
+                    (x * self.width / width) as isize,
+                    (y * self.height / height) as isize,
+                );
+                output.set(x, y, color);
+            }
+        }
+        output
+    }
+
+    // coordinates outside the image are clamped to the edge, an empty
+    // image reads as black
+    pub fn get(&self, x: isize, y: isize) -> Color {
+        if self.pixels.is_empty() {
+            return Color::BLACK;
+        }
+        let x = x.clamp(0, self.width as isize - 1) as usize;
+        let y = y.clamp(0, self.height as isize - 1) as usize;
+        self.pixels[y * self.width + x]
+    }
+
+    pub fn set(&mut self, x: usize, y: usize, color: Color) {
+        self.pixels[y * self.width + x] = color;
+    }
+
+    pub fn to_rgb(&self) -> Vec<u8> {
+        self.pixels
+            .iter()
+            .flat_map(|color| [color.r, color.g, color.b])
+            .collect()
+    }
+
+    // ARGB8888 as SDL reads it on little endian hosts: B, G, R, A
+    pub fn to_argb(&self) -> Vec<u8> {
+        self.pixels
+            .iter()
+            .flat_map(|color| [color.b, color.g, color.r, 0xFF])
+            .collect()
+    }
+}
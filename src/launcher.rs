@@ -0,0 +1,201 @@
+use crate::capture;
+use crate::chip::Rom;
+use crate::database::{self, Program};
+use crate::image::Image;
+use crate::palette::Palette;
+use std::fs;
+use std::io::{self, BufRead, Write};
+use std::path::{Path, PathBuf};
+
+// files kept next to roms that are not roms themselves
+const SIDE_FILES: [&str; 12] = [
+    "meta", "screen", "tones", "png", "gif", "wav", "raw", "toml", "txt", "md", "asm", "8o",
+];
+
+// A rom found by the launcher with what the database knows about it
+pub struct RomInfo {
+    pub path: PathBuf,
+    pub name: String, // file name
+    pub program: Option<Program>,
+}
+
+impl RomInfo {
+    pub fn title(&self) -> &str {
+        match &self.program {
+            Some(program) => &program.title,
+            None => &self.name,
+        }
+    }
+
+    // title, authors, release and description, as far as known
+    pub fn details(&self) -> Vec<String> {
+        let mut lines = vec![self.title().to_string()];
+        if let Some(program) = &self.program {
+            if !program.authors.is_empty() {
+                lines.push(format!("by {}", program.authors.join(", ")));
+            }
+            if let Some(release) = &program.release {
+                lines.push(format!("released {}", release));
+            }
+            if let Some(description) = &program.description {
+                lines.push(description.clone());
+            }
+        } else {
+            lines.push("not in the rom database".to_string());
+        }
+        lines
+    }
+
+    // `<rom>.png` if there is one, otherwise the `<rom>.screen` a test
+    // compares against
+    pub fn preview(&self, palette: &Palette) -> Option<Image> {
+        if let Ok(image) = capture::load_png(&self.path.with_extension("png")) {
+            return Some(image);
+        }
+        let text = self.screen()?;
+        let lines: Vec<&str> = text.lines().collect();
+        // an empty or blank file has nothing to show
+        let width = lines.iter().map(|line| line.chars().count()).max()?;
+        if width == 0 {
+            return None;
+        }
+        let mut image = Image::new(width, lines.len(), palette.color(0));
+        for (y, line) in lines.iter().enumerate() {
+            for (x, c) in line.chars().enumerate() {
+                if c == '#' {
+                    image.set(x, y, palette.color(1));
+                }
+            }
+        }
+        Some(image)
+    }
+
+    fn screen(&self) -> Option<String> {
+        fs::read_to_string(self.path.with_extension("screen")).ok()
+    }
+}
+
+// roms in a directory by name, side files and sub directories skipped
+pub fn scan(dir: &Path) -> Result<Vec<RomInfo>, String> {
+    let error = |e: io::Error| format!("{}: {}", dir.display(), e);
+    let mut roms = Vec::new();
+    for entry in fs::read_dir(dir).map_err(error)? {
+        let path = entry.map_err(error)?.path();
+        let name = match path.file_name().and_then(|name| name.to_str()) {
+            Some(name) if !name.starts_with('.') => name.to_string(),
+            _ => continue,
+        };
+        let extension = path.extension().and_then(|e| e.to_str()).unwrap_or("");
+        if !path.is_file() || SIDE_FILES.contains(&extension.to_lowercase().as_str()) {
+            continue;
+        }
+        let program = fs::File::open(&path)
+            .ok()
+            .and_then(|mut file| Rom::read(&mut file).ok())
+            .and_then(|rom| database::lookup(&rom.sha1))
+            .map(|entry| entry.program);
+        roms.push(RomInfo {
+            path,
+            name,
+            program,
+        });
+    }
+    roms.sort_by_key(|rom| rom.name.to_lowercase());
+    Ok(roms)
+}
+
+// Launcher on the terminal: list the roms, show the details and
+// preview of the chosen one and return it if it should run
+pub fn pick(dir: &str, roms: &[RomInfo]) -> Option<usize> {
+    let stdin = io::stdin();
+    let mut input = stdin.lock().lines();
+    loop {
+        println!("roms in {}:", dir);
+        let width = roms.iter().map(|rom| rom.name.len()).max().unwrap_or(0);
+        for (i, rom) in roms.iter().enumerate() {
+            match &rom.program {
+                Some(program) => println!("{:4}  {:width$}  {}", i + 1, rom.name, program.title),
+                None => println!("{:4}  {}", i + 1, rom.name),
+            }
+        }
+        let line = prompt(&mut input, "number, q to quit: ")?;
+        if line == "q" {
+            return None;
+        }
+        let index = match line.parse::<usize>() {
+            Ok(number) if (1..=roms.len()).contains(&number) => number - 1,
+            _ => continue,
+        };
+
+        let rom = &roms[index];
+        println!();
+        for line in rom.details() {
+            println!("{}", line);
+        }
+        if let Some(screen) = rom.screen() {
+            print!("{}", half_blocks(&screen));
+        }
+        let answer = prompt(&mut input, "run it? [Y/n] ")?;
+        if answer.is_empty() || answer.eq_ignore_ascii_case("y") {
+            return Some(index);
+        }
+    }
+}
+
+// None at the end of the input
+fn prompt(input: &mut impl Iterator<Item = io::Result<String>>, text: &str) -> Option<String> {
+    print!("{}", text);
+    io::stdout().flush().ok()?;
+    Some(input.next()?.ok()?.trim().to_string())
+}
+
+// a screen as printed by `run --headless`, two rows per line of text
+fn half_blocks(screen: &str) -> String {
+    let rows: Vec<Vec<bool>> = screen
+        .lines()
+        .map(|line| line.chars().map(|c| c == '#').collect())
+        .collect();
+    let mut text = String::new();
+    for pair in rows.chunks(2) {
+        for x in 0..pair[0].len() {
+            let top = pair[0][x];
+            let bottom = pair.get(1).is_some_and(|row| row.get(x) == Some(&true));
+            text.push(match (top, bottom) {
+                (true, true) => '█',
+                (true, false) => '▀',
+                (false, true) => '▄',
+                (false, false) => ' ',
+            });
+        }
+        text.push('\n');
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scan_skips_side_files_and_directories() {
+        let dir = std::env::temp_dir().join(format!("chip8-{}-scan", std::process::id()));
+        fs::create_dir_all(dir.join("more")).unwrap();
+        fs::copy("roms/PONG", dir.join("PONG")).unwrap();
+        for name in ["zz.ch8", "PONG.screen", "PONG.PNG", "notes.md", ".hidden"].iter() {
+            fs::write(dir.join(name), [0x12, 0x00]).unwrap();
+        }
+        let roms = scan(&dir).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        let names: Vec<&str> = roms.iter().map(|rom| rom.name.as_str()).collect();
+        assert_eq!(names, ["PONG", "zz.ch8"]);
+        assert_eq!(roms[0].title(), "Pong");
+        assert_eq!(roms[1].title(), "zz.ch8");
+        assert_eq!(roms[1].details()[1], "not in the rom database");
+    }
+
+    #[test]
+    fn screens_print_two_rows_per_line() {
+        assert_eq!(half_blocks("#.#.\n##..\n#\n"), "█▄▀ \n▀\n");
+    }
+}
@@ -1,33 +1,395 @@
+mod asm;
+mod audio;
+mod capture;
 mod chip;
-use chip::Cpu;
+mod cli;
+mod compare;
+mod config;
+mod database;
+mod filter;
+mod frontend;
+mod headless;
+mod image;
+mod launcher;
+mod metadata;
+mod options;
+mod palette;
+mod persistence;
+mod session;
+mod video;
+use audio::{Audio, ToneLog};
+use capture::Recorder;
+use chip::{disassemble, Cpu, Rom, Trace, TraceFilter};
+use clap::{CommandFactory, Parser};
+use cli::{Cli, Command, MachineArgs};
+use frontend::{Exit, Frontend, Menu};
+use options::Options;
+use session::Session;
 use std::env;
 use std::fs;
+use std::path::Path;
+use std::path::PathBuf;
 use std::process;
+use std::time::Instant;
 
+// exit codes, clap uses 2 for usage errors
+const EXIT_RUNTIME: i32 = 1;
+const EXIT_LOAD: i32 = 3;
 
 fn main() {
-    let args: Vec<String> = env::args().collect();
-    if args.len() != 2 {
-        eprintln!("chip8 <Rom>");
-        process::exit(-1)
+    // `chip8 roms/PONG` still works as a shorthand for `chip8 run roms/PONG`
+    let mut args: Vec<String> = env::args().collect();
+    if args.len() > 1 && !is_subcommand(&args[1]) {
+        args.insert(1, "run".to_string());
     }
 
-    let rom_file = &args[1];
+    match Cli::parse_from(args).command {
+        Command::Run {
+            rom,
+            machine,
+            headless,
+            screenshot,
+            record,
+            record_frames,
+            record_audio,
+            native,
+            tones,
+            watch,
+            trace,
+            trace_range,
+            trace_only,
+            trace_last,
+        } => {
+            let mut session = boot(&rom, &machine);
+            if let Some(file) = &trace {
+                let filter = TraceFilter {
+                    range: trace_range,
+                    classes: trace_only,
+                };
+                match Trace::create(file, filter, trace_last.map(|n| n as usize)) {
+                    Ok(trace) => session.cpu.trace = Some(trace),
+                    Err(e) => {
+                        eprintln!("{}", e);
+                        process::exit(EXIT_LOAD);
+                    }
+                }
+            }
+            let options = session.options.clone();
+            let mut recorder = Recorder::new(native);
+            if let Some(file) = &record {
+                recorder.start_gif(Path::new(file));
+            }
+            if let Err(e) = start_recording(&mut recorder, &options, record_frames, record_audio) {
+                eprintln!("{}", e);
+                process::exit(EXIT_LOAD);
+            }
+            let mut tone_log = Audio::new(ToneLog::default(), &options);
 
-    let mut f = match fs::File::open(rom_file) {
-        Ok(handle) => handle,
-        Err(e) => {
-            eprintln!("{}", e);
-            process::exit(-1);
+            let result = match headless {
+                Some(frames) => {
+                    let video = &mut session.video;
+                    headless::run(&mut session.cpu, frames, |cpu| {
+                        video.update(cpu);
+                        recorder.frame(video, cpu);
+                        tone_log.frame(&cpu.sound_events()).unwrap();
+                    })
+                }
+                //start execution
+                None => {
+                    let mut frontend = Frontend::new(options);
+                    if watch {
+                        frontend.watch(&rom);
+                    }
+                    session
+                        .play(&mut frontend, &machine, &mut recorder)
+                        .map(|_| ())
+                }
+            };
+            // process::exit skips destructors, which would flush it
+            if let Some(trace) = &mut session.cpu.trace {
+                if let Err(e) = trace.flush() {
+                    eprintln!("trace: {}", e);
+                }
+            }
+            let (cpu, video) = (&session.cpu, &session.video);
+            recorder.finish();
+            if let Err(e) = result {
+                cpu.report(&e);
+                process::exit(EXIT_RUNTIME);
+            }
+            if tones {
+                print!("{}", tone_log.sink().to_text());
+            } else if headless.is_some() {
+                print!("{}", cpu.display.to_text());
+            }
+            // the final screen, once the rom stopped or the window closed
+            if let Some(file) = screenshot {
+                let path = file
+                    .map(PathBuf::from)
+                    .unwrap_or_else(|| capture::timestamped("screenshot", "png"));
+                if let Err(e) = capture::save_png(&video.capture(&cpu.display, native), &path) {
+                    eprintln!("{}", e);
+                    process::exit(EXIT_RUNTIME);
+                }
+            }
         }
-    };
+        Command::Browse {
+            dir,
+            machine,
+            terminal,
+        } => browse(&dir, &machine, terminal),
+        Command::Disasm { rom, machine } => {
+            let data = read_rom(&rom);
+            let options = machine_options(&machine, &rom, &data);
+            let origin = options.memory_map.load_address;
+            for (i, chunk) in data.data.chunks(2).enumerate() {
+                let address = origin + i * 2;
+                match chunk {
+                    [high, low] => {
+                        let opcode = (*high as u16) << 8 | *low as u16;
+                        println!("{:#05X}  {:04X}  {}", address, opcode, disassemble(opcode));
+                    }
+                    [byte] => println!("{:#05X}  {:02X}    DB {:#04X}", address, byte, byte),
+                    _ => unreachable!(),
+                }
+            }
+        }
+        Command::Asm {
+            source,
+            output,
+            origin,
+        } => {
+            let text = fs::read_to_string(&source).unwrap_or_else(|e| {
+                eprintln!("{}: {}", source, e);
+                process::exit(EXIT_LOAD)
+            });
+            let program = asm::assemble(&text, origin).unwrap_or_else(|e| {
+                eprintln!("{}: {}", source, e);
+                process::exit(EXIT_LOAD)
+            });
+            if let Err(e) = fs::write(&output, program) {
+                eprintln!("{}: {}", output, e);
+                process::exit(EXIT_RUNTIME);
+            }
+        }
+        Command::Info { rom } => {
+            let data = read_rom(&rom);
+            match database::lookup(&data.sha1) {
+                Some(entry) => entry.print(),
+                None => println!("sha1:     {}\nnot found in the rom database", data.sha1),
+            }
+        }
+        Command::Test {
+            roms,
+            machine,
+            frames,
+        } => {
+            let mut failed = 0;
+            for rom in &roms {
+                if let Err(e) = test(rom, &machine, frames) {
+                    println!("FAIL {}: {}", rom, e);
+                    failed += 1;
+                } else {
+                    println!("ok   {}", rom);
+                }
+            }
+            println!("{} passed, {} failed", roms.len() - failed, failed);
+            if failed > 0 {
+                process::exit(EXIT_RUNTIME);
+            }
+        }
+        Command::Bench {
+            rom,
+            machine,
+            frames,
+        } => {
+            let mut cpu = boot(&rom, &machine).cpu;
+            let t0 = Instant::now();
+            if let Err(e) = headless::run(&mut cpu, frames, |_| {}) {
+                cpu.report(&e);
+                process::exit(EXIT_RUNTIME);
+            }
+            let seconds = t0.elapsed().as_secs_f64();
+            println!("frames:       {}", frames);
+            println!("instructions: {}", cpu.cycles());
+            println!("time:         {:.3}s", seconds);
+            println!(
+                "speed:        {:.0} instructions/s",
+                cpu.cycles() as f64 / seconds
+            );
+            println!(
+                "              {:.1}x real time",
+                frames as f64 / 60.0 / seconds
+            );
+        }
+        Command::Compare {
+            rom,
+            reference,
+            machine,
+            frames,
+        } => {
+            let reference = compare::read(&reference).unwrap_or_else(|e| {
+                eprintln!("{}", e);
+                process::exit(EXIT_LOAD)
+            });
+            let mut cpu = boot(&rom, &machine).cpu;
+            match compare::run(&mut cpu, &reference, frames) {
+                Ok(true) => {}
+                Ok(false) => process::exit(EXIT_RUNTIME),
+                Err(e) => {
+                    cpu.report(&e);
+                    process::exit(EXIT_RUNTIME);
+                }
+            }
+        }
+    }
+}
+
+// a subcommand clap knows, `help` included, or a flag like --help or -V
+fn is_subcommand(arg: &str) -> bool {
+    let mut command = Cli::command();
+    command.build();
+    arg.starts_with('-')
+        || command
+            .get_subcommands()
+            .any(|subcommand| subcommand.get_name() == arg)
+}
+
+fn read_rom(rom_file: &str) -> Rom {
+    fs::File::open(rom_file)
+        .map_err(Into::into)
+        .and_then(|mut f| Rom::read(&mut f))
+        .unwrap_or_else(|e| {
+            eprintln!("{}: {}", rom_file, e);
+            process::exit(EXIT_LOAD)
+        })
+}
+
+fn machine_options(machine: &MachineArgs, rom_file: &str, rom: &Rom) -> Options {
+    machine.options(rom_file, rom).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        process::exit(EXIT_LOAD)
+    })
+}
+
+// read the rom and create a cpu with it loaded
+fn boot(rom_file: &str, machine: &MachineArgs) -> Session {
+    Session::boot(rom_file, machine).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        process::exit(EXIT_LOAD)
+    })
+}
+
+// The launcher: roms run one after the other in the same process, in
+// one window when the list is shown in it. A rom that fails is
+// reported and the list comes back.
+fn browse(dir: &str, machine: &MachineArgs, terminal: bool) {
+    let roms = launcher::scan(Path::new(dir)).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        process::exit(EXIT_LOAD)
+    });
+    if roms.is_empty() {
+        eprintln!("{}: no roms found", dir);
+        process::exit(EXIT_LOAD);
+    }
 
-    //create new cpu (chip 8)
-    let mut cpu: Cpu = Cpu::new();
+    if terminal {
+        while let Some(index) = launcher::pick(dir, &roms) {
+            let rom_file = roms[index].path.to_string_lossy();
+            match Session::boot(&rom_file, machine) {
+                Ok(mut session) => {
+                    let mut frontend = Frontend::new(session.options.clone());
+                    let mut recorder = Recorder::new(false);
+                    if let Err(e) = session.play(&mut frontend, machine, &mut recorder) {
+                        session.cpu.report(&e);
+                    }
+                }
+                Err(e) => eprintln!("{}", e),
+            }
+            println!();
+        }
+        return;
+    }
 
-    //load rom into cpu
-    cpu.load(&mut f);
+    let options = machine.base_options().unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        process::exit(EXIT_LOAD)
+    });
+    let mut menu = Menu::new(dir, roms, &options.palette);
+    let mut frontend = Frontend::new(options);
+    while frontend.browse(&mut menu) {
+        menu.status = None;
+        let rom_file = menu.roms[menu.selected].path.to_string_lossy().to_string();
+        let mut session = match Session::boot(&rom_file, machine) {
+            Ok(session) => session,
+            Err(e) => {
+                menu.status = Some(e);
+                continue;
+            }
+        };
+        frontend.configure(session.options.clone());
+        match session.play(&mut frontend, machine, &mut Recorder::new(false)) {
+            Ok(Exit::Quit) => break,
+            Ok(_) => {}
+            Err(e) => {
+                session.cpu.report(&e);
+                menu.status = Some(format!("{}: {}", session.rom_file, e));
+            }
+        }
+    }
+}
+
+fn start_recording(
+    recorder: &mut Recorder,
+    options: &Options,
+    frames: Option<String>,
+    audio: Option<String>,
+) -> Result<(), String> {
+    if let Some(file) = frames {
+        recorder.start_raw(Path::new(&file))?;
+    }
+    if let Some(file) = audio {
+        recorder.start_wav(Path::new(&file), options)?;
+    }
+    Ok(())
+}
+
+// a rom passes when it runs without error and, if `<rom>.screen` exists,
+// ends with that screen (as printed by `run --headless`). The same goes
+// for `<rom>.tones` and the sound it made (`run --headless --tones`).
+fn test(rom_file: &str, machine: &MachineArgs, frames: u64) -> Result<(), String> {
+    let rom = fs::File::open(rom_file)
+        .map_err(Into::into)
+        .and_then(|mut f| Rom::read(&mut f))
+        .map_err(|e| e.to_string())?;
+    let options = machine.options(rom_file, &rom)?;
+    let mut cpu = Cpu::new(&options);
+    cpu.load(&rom).map_err(|e| e.to_string())?;
+    let mut tone_log = Audio::new(ToneLog::default(), &options);
+    headless::run(&mut cpu, frames, |cpu| {
+        tone_log.frame(&cpu.sound_events()).unwrap()
+    })
+    .map_err(|e| e.to_string())?;
+
+    let expected = Path::new(rom_file).with_extension("screen");
+    if let Ok(screen) = fs::read_to_string(&expected) {
+        if screen != cpu.display.to_text() {
+            return Err(format!("screen differs from {}", expected.display()));
+        }
+    }
+    let expected = Path::new(rom_file).with_extension("tones");
+    if let Ok(tones) = fs::read_to_string(&expected) {
+        if tones != tone_log.sink().to_text() {
+            return Err(format!("tones differ from {}", expected.display()));
+        }
+    }
+    Ok(())
+}
 
-    //start execution
-    cpu.execute();
+// accepts 0x050, 050 or 50 (always hexadecimal)
+pub fn parse_address(text: &str) -> Option<usize> {
+    let digits = text.trim_start_matches("0x").trim_start_matches("0X");
+    usize::from_str_radix(digits, 16)
+        .ok()
+        .filter(|&a| a < 0x1000)
 }
@@ -0,0 +1,84 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// Optional per-rom settings read from a `<rom>.meta` file next to the rom:
+//
+//   # lines are `key = value`, addresses in hex
+//   platform = eti660
+//   load_address = 0x600
+//   entry_point = 0x600
+#[derive(Default)]
+pub struct RomMetadata {
+    pub load_address: Option<usize>,
+    pub entry_point: Option<usize>,
+}
+
+impl RomMetadata {
+    pub fn path_for(rom_file: &str) -> PathBuf {
+        Path::new(rom_file).with_extension("meta")
+    }
+
+    // a missing file is not an error, a malformed one is
+    pub fn read(rom_file: &str) -> Result<Self, String> {
+        let path = Self::path_for(rom_file);
+        match fs::read_to_string(&path) {
+            Ok(text) => Self::parse(&text).map_err(|e| format!("{}: {}", path.display(), e)),
+            Err(_) => Ok(Self::default()),
+        }
+    }
+
+    fn parse(text: &str) -> Result<Self, String> {
+        let mut metadata = Self::default();
+        for (number, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap().trim();
+            if line.is_empty() {
+                continue;
+            }
+            let (key, value) = match line.split_once('=') {
+                Some((key, value)) => (key.trim(), value.trim()),
+                None => return Err(format!("line {}: expected `key = value`", number + 1)),
+            };
+            let address = || {
+                crate::parse_address(value)
+                    .ok_or_else(|| format!("line {}: invalid address `{}`", number + 1, value))
+            };
+            match key {
+                "load_address" => metadata.load_address = Some(address()?),
+                "entry_point" => metadata.entry_point = Some(address()?),
+                "platform" => match value {
+                    "chip8" | "vip" => metadata.load_address = Some(crate::chip::PROGRAM_START),
+                    "eti660" => metadata.load_address = Some(crate::chip::ETI660_PROGRAM_START),
+                    _ => return Err(format!("line {}: unknown platform `{}`", number + 1, value)),
+                },
+                _ => return Err(format!("line {}: unknown key `{}`", number + 1, key)),
+            }
+        }
+        Ok(metadata)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_addresses_and_platforms() {
+        let metadata =
+            RomMetadata::parse("# eti\nplatform = eti660\nentry_point = 0x604\n").unwrap();
+        assert_eq!(metadata.load_address, Some(0x600));
+        assert_eq!(metadata.entry_point, Some(0x604));
+
+        // later lines win
+        let metadata = RomMetadata::parse("platform = eti660\nload_address = 300").unwrap();
+        assert_eq!(metadata.load_address, Some(0x300));
+    }
+
+    #[test]
+    fn reports_the_line_at_fault() {
+        let error = |text| RomMetadata::parse(text).err().unwrap();
+        assert_eq!(error("\nload_address"), "line 2: expected `key = value`");
+        assert_eq!(error("load_address = zz"), "line 1: invalid address `zz`");
+        assert_eq!(error("platform = c64"), "line 1: unknown platform `c64`");
+        assert_eq!(error("speed = 10"), "line 1: unknown key `speed`");
+    }
+}
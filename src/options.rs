@@ -0,0 +1,62 @@
+use crate::audio::Waveform;
+use crate::chip::{MemoryMap, Quirks};
+use crate::filter::Pipeline;
+use crate::frontend::{Hotkeys, Keymap};
+use crate::palette::Palette;
+use crate::persistence::Persistence;
+
+// Machine and frontend settings chosen before the cpu is created
+#[derive(Clone)]
+pub struct Options {
+    pub stack_depth: usize, // 12 on the COSMAC VIP, 16 on SCHIP
+    pub stack_in_ram: bool, // keep the stack in ram at the VIP's address
+    pub memory_map: MemoryMap,
+    pub quirks: Quirks,
+    pub tickrate: u32,     // instructions per frame
+    pub seed: Option<u64>, // fixed seed for CXKK, random when None
+    pub keymap: Keymap,
+    pub hotkeys: Hotkeys,
+    pub scale: u32, // window pixels per chip 8 pixel
+    pub fullscreen: bool,
+    pub integer_scale: bool, // scale the window by whole multiples only
+    pub palette: Palette,
+    pub persistence: Persistence, // flicker reduction
+    pub filter: Pipeline,         // software upscaling and effects
+    pub hud: bool,                // fps, ips, pc and keys over the game
+    pub keypad: bool,             // clickable keypad beside the game
+    pub waveform: Waveform,
+    pub tone: f32,    // beeper frequency in Hz
+    pub volume: f32,  // 0.0 to 1.0
+    pub attack: f32,  // beeper fade in, milliseconds
+    pub release: f32, // beeper fade out, milliseconds
+    pub mute: bool,   // start muted
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            stack_depth: 16,
+            stack_in_ram: false,
+            memory_map: MemoryMap::default(),
+            quirks: Quirks::default(),
+            tickrate: 16,
+            seed: None,
+            keymap: Keymap::default(),
+            hotkeys: Hotkeys::default(),
+            scale: 16,
+            fullscreen: false,
+            integer_scale: false,
+            palette: Palette::default(),
+            persistence: Persistence::Off,
+            filter: Pipeline::default(),
+            hud: false,
+            keypad: false,
+            waveform: Waveform::Square,
+            tone: 440.0,
+            volume: 0.25,
+            attack: 2.0,
+            release: 5.0,
+            mute: false,
+        }
+    }
+}
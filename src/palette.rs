@@ -0,0 +1,153 @@
+use sdl2::pixels::Color;
+
+// Built in themes: background, foreground, then the colours used when
+// two planes are drawn (plane 2 only, both planes) in extended modes.
+const THEMES: [(&str, [u32; 4]); 7] = [
+    ("purple", [0x000000, 0x642764, 0xA04AA0, 0xFFFFFF]),
+    ("green", [0x001100, 0x33FF33, 0x1A801A, 0xB3FFB3]),
+    ("amber", [0x1A0F00, 0xFFB000, 0x805800, 0xFFE0A0]),
+    ("lcd", [0x9BBC0F, 0x0F380F, 0x306230, 0x8BAC0F]),
+    ("high-contrast", [0x000000, 0xFFFFFF, 0xFFFF00, 0x00FFFF]),
+    ("octo", [0x996600, 0xFFCC00, 0xFF6600, 0x662200]),
+    ("white", [0xFFFFFF, 0x000000, 0x808080, 0x404040]),
+];
+
+// Colours for the logical pixel values of the framebuffer
+#[derive(Clone)]
+pub struct Palette {
+    pub name: Option<&'static str>, // None for custom colours
+    pub colors: [Color; 4],
+}
+
+impl Palette {
+    pub fn named(name: &str) -> Option<Self> {
+        let (name, colors) = THEMES.iter().find(|(theme, _)| *theme == name)?;
+        Some(Self {
+            name: Some(name),
+            colors: colors.map(rgb),
+        })
+    }
+
+    pub fn names() -> Vec<&'static str> {
+        THEMES.iter().map(|(name, _)| *name).collect()
+    }
+
+    // A theme name or a list of hex colours, background first:
+    // "amber" or "#000000,#33FF66". Missing plane colours keep this
+    // palette's values.
+    pub fn parse(&self, text: &str) -> Option<Self> {
+        if let Some(palette) = Self::named(text) {
+            return Some(palette);
+        }
+        let colors = text
+            .split(',')
+            .map(|color| parse_color(color.trim()))
+            .collect::<Option<Vec<_>>>()?;
+        self.with_colors(&colors)
+    }
+
+    // replace the first colours, at least background and foreground
+    pub fn with_colors(&self, colors: &[Color]) -> Option<Self> {
+        if colors.len() < 2 || colors.len() > 4 {
+            return None;
+        }
+        let mut palette = Self {
+            name: None,
+            colors: self.colors,
+        };
+        palette.colors[..colors.len()].copy_from_slice(colors);
+        Some(palette)
+    }
+
+    // the theme after this one, wrapping around
+    pub fn next(&self) -> Self {
+        let index = THEMES
+            .iter()
+            .position(|(theme, _)| Some(*theme) == self.name)
+            .map_or(0, |index| (index + 1) % THEMES.len());
+        Self::named(THEMES[index].0).unwrap()
+    }
+
+    pub fn color(&self, pixel: u8) -> Color {
+        self.colors[pixel as usize & 3]
+    }
+
+    // pixel colour faded towards the background, level 255 is fully lit
+    pub fn blend(&self, pixel: u8, level: u8) -> Color {
+        let (from, to) = (self.colors[0], self.color(pixel));
+        let mix = |a: u8, b: u8| {
+            ((a as u32 * (255 - level as u32) + b as u32 * level as u32) / 255) as u8
+        };
+        Color::RGB(mix(from.r, to.r), mix(from.g, to.g), mix(from.b, to.b))
+    }
+}
+
+impl Default for Palette {
+    fn default() -> Self {
+        Self::named("purple").unwrap()
+    }
+}
+
+fn rgb(value: u32) -> Color {
+    Color::RGB((value >> 16) as u8, (value >> 8) as u8, value as u8)
+}
+
+// "#RRGGBB"
+pub fn parse_color(text: &str) -> Option<Color> {
+    let hex = text.strip_prefix('#')?;
+    if hex.len() != 6 {
+        return None;
+    }
+    let value = u32::from_str_radix(hex, 16).ok()?;
+    Some(rgb(value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_hex_colors() {
+        assert_eq!(parse_color("#FF8000"), Some(Color::RGB(255, 128, 0)));
+        assert_eq!(parse_color("FF8000"), None);
+        assert_eq!(parse_color("#FFF"), None);
+    }
+
+    #[test]
+    fn hex_lists_replace_the_first_colours() {
+        let amber = Palette::named("amber").unwrap();
+        let palette = amber.parse("#000000, #33FF66").unwrap();
+        assert_eq!(palette.name, None);
+        assert_eq!(palette.colors[0], rgb(0x000000));
+        assert_eq!(palette.colors[1], rgb(0x33FF66));
+        assert_eq!(palette.colors[2..], amber.colors[2..]);
+        assert!(amber.parse("#000000").is_none());
+        assert!(amber
+            .parse("#000000,#111111,#222222,#333333,#444444")
+            .is_none());
+        assert!(amber.parse("plaid").is_none());
+    }
+
+    #[test]
+    fn cycling_visits_every_theme_and_wraps() {
+        let mut palette = Palette::default();
+        let mut seen = vec![palette.name.unwrap()];
+        for _ in 1..THEMES.len() {
+            palette = palette.next();
+            seen.push(palette.name.unwrap());
+        }
+        assert_eq!(seen, Palette::names());
+        assert_eq!(palette.next().name, Some("purple"));
+        // a custom palette starts over at the first theme
+        let custom = palette.parse("#000000,#FFFFFF").unwrap();
+        assert_eq!(custom.next().name, Some("purple"));
+    }
+
+    #[test]
+    fn logical_pixels_pick_a_colour() {
+        let palette = Palette::named("white").unwrap();
+        assert_eq!(palette.color(0), rgb(0xFFFFFF));
+        assert_eq!(palette.color(1), rgb(0x000000));
+        assert_eq!(palette.color(3), rgb(0x404040));
+    }
+}
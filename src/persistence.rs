@@ -0,0 +1,214 @@
+use crate::chip::Display;
+use std::collections::VecDeque;
+
+pub const MODES: [&str; 4] = ["off", "phosphor", "blend", "draw"];
+
+// How the logical framebuffer is turned into the frame that is shown,
+// to hide the flicker of sprites erased and redrawn with XOR
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub enum Persistence {
+    #[default]
+    Off,
+    Phosphor(u8), // lit pixels fade out over this many frames
+    Blend(u8),    // average of the last frames
+    Draw,         // only show frames in which the rom drew
+}
+
+impl Persistence {
+    // "phosphor", "phosphor:6", "blend:3", "draw" or "off"
+    pub fn parse(text: &str) -> Option<Self> {
+        let (mode, frames) = match text.split_once(':') {
+            Some((mode, frames)) => (mode, Some(frames.parse::<u8>().ok()?)),
+            None => (text, None),
+        };
+        if frames == Some(0) {
+            return None;
+        }
+        match mode {
+            "off" if frames.is_none() => Some(Persistence::Off),
+            "phosphor" => Some(Persistence::Phosphor(frames.unwrap_or(4))),
+            "blend" => Some(Persistence::Blend(frames.unwrap_or(2))),
+            "draw" if frames.is_none() => Some(Persistence::Draw),
+            _ => None,
+        }
+    }
+}
+
+// Logical frame after filtering. A pixel keeps the last value it was
+// lit with while it fades, `levels` says how far: 255 fully lit, 0 gone.
+pub struct Frame {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<u8>,
+    pub levels: Vec<u8>,
+}
+
+impl Frame {
+    fn new(width: usize, height: usize) -> Self {
+        Self {
+            width,
+            height,
+            pixels: vec![0; width * height],
+            levels: vec![0; width * height],
+        }
+    }
+}
+
+pub struct Filter {
+    mode: Persistence,
+    frame: Frame,
+    history: VecDeque<Vec<u8>>, // most recent buffer last, for Blend
+}
+
+impl Filter {
+    // starts on a blank screen, so a capture taken before the first
+    // frame still has the display's size
+    pub fn new(mode: Persistence) -> Self {
+        let blank = Display::new();
+        Self {
+            mode,
+            frame: Frame::new(blank.width(), blank.height()),
+            history: VecDeque::new(),
+        }
+    }
+
+    // back to a blank screen with nothing fading out
+    pub fn clear(&mut self) {
+        *self = Self::new(self.mode);
+    }
+
+    pub fn frame(&self) -> &Frame {
+        &self.frame
+    }
+
+    // feed the display after a frame, `drew` as reported by the cpu
+    pub fn apply(&mut self, display: &Display, drew: bool) -> &Frame {
+        let buffer = &display.buffer;
+        if self.frame.pixels.len() != buffer.len() {
+            self.frame = Frame::new(display.width(), display.height());
+            self.history.clear();
+        }
+        let frame = &mut self.frame;
+
+        match self.mode {
+            Persistence::Off => copy(frame, buffer),
+            Persistence::Draw => {
+                if drew {
+                    copy(frame, buffer);
+                }
+            }
+            Persistence::Phosphor(frames) => {
+                let step = (255 / frames as u16) as u8;
+                for (i, pixel) in buffer.iter().enumerate() {
+                    if *pixel != 0 {
+                        frame.pixels[i] = *pixel;
+                        frame.levels[i] = 255;
+                    } else {
+                        frame.levels[i] = frame.levels[i].saturating_sub(step);
+                    }
+                }
+            }
+            Persistence::Blend(frames) => {
+                if self.history.len() == frames as usize {
+                    self.history.pop_front();
+                }
+                self.history.push_back(buffer.clone());
+                for i in 0..buffer.len() {
+                    let lit = self.history.iter().filter(|b| b[i] != 0).count();
+                    // most recent value the pixel was lit with
+                    if let Some(b) = self.history.iter().rev().find(|b| b[i] != 0) {
+                        frame.pixels[i] = b[i];
+                    }
+                    frame.levels[i] = (lit * 255 / frames as usize) as u8;
+                }
+            }
+        }
+        &self.frame
+    }
+}
+
+fn copy(frame: &mut Frame, buffer: &[u8]) {
+    frame.pixels.copy_from_slice(buffer);
+    for (level, pixel) in frame.levels.iter_mut().zip(buffer) {
+        *level = if *pixel != 0 { 255 } else { 0 };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lit(x: usize, y: usize) -> Display {
+        let mut display = Display::new();
+        display.set_pixel(x, y);
+        display
+    }
+
+    #[test]
+    fn parses_modes_with_optional_frames() {
+        assert_eq!(Persistence::parse("off"), Some(Persistence::Off));
+        assert_eq!(
+            Persistence::parse("phosphor"),
+            Some(Persistence::Phosphor(4))
+        );
+        assert_eq!(
+            Persistence::parse("phosphor:6"),
+            Some(Persistence::Phosphor(6))
+        );
+        assert_eq!(Persistence::parse("blend:3"), Some(Persistence::Blend(3)));
+        assert_eq!(Persistence::parse("draw"), Some(Persistence::Draw));
+        assert_eq!(Persistence::parse("blend:0"), None);
+        assert_eq!(Persistence::parse("draw:2"), None);
+        assert_eq!(Persistence::parse("glow"), None);
+    }
+
+    #[test]
+    fn off_shows_the_display_as_is() {
+        let mut filter = Filter::new(Persistence::Off);
+        let frame = filter.apply(&lit(3, 2), true);
+        assert_eq!(frame.pixels[2 * 64 + 3], 1);
+        assert_eq!(frame.levels[2 * 64 + 3], 255);
+        let frame = filter.apply(&Display::new(), true);
+        assert_eq!(frame.levels[2 * 64 + 3], 0);
+    }
+
+    #[test]
+    fn phosphor_fades_an_erased_pixel_over_its_frames() {
+        let mut filter = Filter::new(Persistence::Phosphor(4));
+        filter.apply(&lit(0, 0), true);
+        let mut levels = Vec::new();
+        for _ in 0..5 {
+            levels.push(filter.apply(&Display::new(), true).levels[0]);
+        }
+        assert_eq!(levels, vec![192, 129, 66, 3, 0]);
+        // the colour stays while it fades
+        assert_eq!(filter.frame().pixels[0], 1);
+    }
+
+    #[test]
+    fn blend_averages_the_last_frames() {
+        let mut filter = Filter::new(Persistence::Blend(2));
+        assert_eq!(filter.apply(&lit(0, 0), true).levels[0], 127);
+        assert_eq!(filter.apply(&lit(0, 0), true).levels[0], 255);
+        assert_eq!(filter.apply(&Display::new(), true).levels[0], 127);
+        assert_eq!(filter.apply(&Display::new(), true).levels[0], 0);
+    }
+
+    #[test]
+    fn draw_keeps_the_last_frame_drawn_to() {
+        let mut filter = Filter::new(Persistence::Draw);
+        filter.apply(&lit(5, 5), true);
+        // erased mid-redraw, the frame did not finish drawing
+        assert_eq!(filter.apply(&Display::new(), false).levels[5 * 64 + 5], 255);
+        assert_eq!(filter.apply(&Display::new(), true).levels[5 * 64 + 5], 0);
+    }
+
+    #[test]
+    fn starts_and_clears_to_a_blank_screen() {
+        let mut filter = Filter::new(Persistence::Phosphor(4));
+        assert_eq!((filter.frame().width, filter.frame().height), (64, 32));
+        filter.apply(&lit(0, 0), true);
+        filter.clear();
+        assert!(filter.frame().levels.iter().all(|level| *level == 0));
+    }
+}
@@ -0,0 +1,149 @@
+use crate::capture::Recorder;
+use crate::chip::{Cpu, ExecError, Rom};
+use crate::cli::MachineArgs;
+use crate::frontend::{Exit, Frontend};
+use crate::options::Options;
+use crate::video::Video;
+use std::fs;
+
+// A rom loaded into a cpu, with what it takes to start it over
+pub struct Session {
+    pub rom_file: String,
+    pub options: Options,
+    pub cpu: Cpu,
+    pub video: Video,
+    rom: Rom,
+}
+
+impl Session {
+    // read the rom and create a cpu with it loaded
+    pub fn boot(rom_file: &str, machine: &MachineArgs) -> Result<Self, String> {
+        let rom = fs::File::open(rom_file)
+            .map_err(Into::into)
+            .and_then(|mut f| Rom::read(&mut f))
+            .map_err(|e| format!("{}: {}", rom_file, e))?;
+        let options = machine.options(rom_file, &rom)?;
+        let mut cpu = Cpu::new(&options);
+        cpu.load(&rom).map_err(|e| format!("{}: {}", rom_file, e))?;
+        Ok(Self {
+            rom_file: rom_file.to_string(),
+            video: Video::new(&options),
+            options,
+            cpu,
+            rom,
+        })
+    }
+
+    // Run in the window until the user quits or goes back to the
+    // launcher. Hard resets, reloads and dropped files replace the cpu
+    // on the way without closing the window. A file that does not load
+    // is reported and the current rom goes on.
+    pub fn play(
+        &mut self,
+        frontend: &mut Frontend,
+        machine: &MachineArgs,
+        recorder: &mut Recorder,
+    ) -> Result<Exit, ExecError> {
+        loop {
+            let (next, done) = match frontend.run(&mut self.cpu, &mut self.video, recorder)? {
+                Exit::HardReset => {
+                    self.hard_reset();
+                    frontend.reset_audio();
+                    recorder.reset_audio();
+                    frontend.message("Hard reset".to_string());
+                    continue;
+                }
+                Exit::Reload => (Self::boot(&self.rom_file, machine), "Reloaded"),
+                Exit::Open(path) => (Self::boot(&path.to_string_lossy(), machine), "Loaded"),
+                exit => return Ok(exit),
+            };
+            match next {
+                Ok(mut session) => {
+                    // a dropped file is watched from now on
+                    if frontend.watching() {
+                        frontend.watch(&session.rom_file);
+                    }
+                    frontend.configure(session.options.clone());
+                    frontend.reset_audio();
+                    recorder.reset_audio();
+                    frontend.message(format!("{} {}", done, session.rom_file));
+                    // the trace goes on in the same file
+                    session.cpu.trace = self.cpu.trace.take();
+                    *self = session;
+                }
+                Err(e) => {
+                    eprintln!("{}", e);
+                    frontend.message(e);
+                }
+            }
+        }
+    }
+
+    // a new cpu as `boot` made it on a blank screen, the picture
+    // settings are kept
+    fn hard_reset(&mut self) {
+        let mut cpu = Cpu::new(&self.options);
+        cpu.load(&self.rom).expect("the rom loaded before");
+        cpu.trace = self.cpu.trace.take();
+        self.cpu = cpu;
+        self.video.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::{Cli, Command};
+    use clap::Parser;
+    use std::path::PathBuf;
+
+    // a rom file of its own, with an empty config so no user settings apply
+    fn machine(name: &str, rom: &[u8]) -> (String, MachineArgs) {
+        let dir = std::env::temp_dir().join(format!("chip8-{}-{}", std::process::id(), name));
+        fs::create_dir_all(&dir).unwrap();
+        let config: PathBuf = dir.join("chip8.toml");
+        fs::write(&config, "").unwrap();
+        let rom_file = dir.join("rom.ch8").to_string_lossy().into_owned();
+        fs::write(&rom_file, rom).unwrap();
+        let args = [
+            "chip8",
+            "run",
+            "--config",
+            config.to_str().unwrap(),
+            &rom_file,
+        ];
+        match Cli::try_parse_from(args).unwrap().command {
+            Command::Run { machine, .. } => (rom_file, machine),
+            _ => unreachable!(),
+        }
+    }
+
+    // V0 = 1, then count up in a loop
+    const COUNTER: [u8; 6] = [0x60, 0x01, 0x70, 0x01, 0x12, 0x02];
+
+    #[test]
+    fn hard_reset_starts_the_rom_over() {
+        let (rom_file, machine) = machine("hard-reset", &COUNTER);
+        let mut session = Session::boot(&rom_file, &machine).unwrap();
+        session.cpu.run_frame().unwrap();
+        assert_eq!((session.cpu.pc(), session.cpu.cycles()), (0x204, 16));
+        session.hard_reset();
+        assert_eq!((session.cpu.pc(), session.cpu.cycles()), (0x200, 0));
+    }
+
+    #[test]
+    fn boot_reads_the_rom_file_again() {
+        let (rom_file, machine) = machine("reload", &COUNTER);
+        let session = Session::boot(&rom_file, &machine).unwrap();
+        // a rom that only jumps to 0x202
+        fs::write(&rom_file, [0x12, 0x02, 0x12, 0x02]).unwrap();
+        let mut reloaded = Session::boot(&rom_file, &machine).unwrap();
+        reloaded.cpu.run_frame().unwrap();
+        assert_eq!(reloaded.cpu.pc(), 0x202);
+        assert_ne!(reloaded.rom.sha1, session.rom.sha1);
+
+        fs::remove_file(&rom_file).unwrap();
+        let error = Session::boot(&rom_file, &machine).err().unwrap();
+        assert!(error.starts_with(&rom_file), "{}", error);
+    }
+}
@@ -0,0 +1,101 @@
+use crate::chip::{Cpu, Display};
+use crate::filter::Pipeline;
+use crate::image::Image;
+use crate::options::Options;
+use crate::palette::Palette;
+use crate::persistence::Filter;
+
+// Turns the display into the picture that is shown, saved or recorded:
+// persistence, then palette, then the filter pipeline
+pub struct Video {
+    pub palette: Palette,
+    pub pipeline: Pipeline,
+    persistence: Filter,
+    scale: u32, // window pixels per chip 8 pixel, for scaled screenshots
+}
+
+impl Video {
+    pub fn new(options: &Options) -> Self {
+        Self {
+            palette: options.palette.clone(),
+            pipeline: options.filter.clone(),
+            persistence: Filter::new(options.persistence),
+            scale: options.scale,
+        }
+    }
+
+    // feed the display once per frame, after the cpu ran
+    pub fn update(&mut self, cpu: &Cpu) {
+        self.persistence.apply(&cpu.display, cpu.drew());
+    }
+
+    // forget the frames shown so far, so no afterglow of them is left
+    // over a restarted rom; palette and filters stay as chosen
+    pub fn clear(&mut self) {
+        self.persistence.clear();
+    }
+
+    // chip 8 pixels of the last frame
+    pub fn resolution(&self) -> (usize, usize) {
+        let frame = self.persistence.frame();
+        (frame.width, frame.height)
+    }
+
+    pub fn image(&self) -> Image {
+        let frame = self.persistence.frame();
+        self.pipeline.apply(Image::from_frame(frame, &self.palette))
+    }
+
+    // For screenshots and recordings. Native is one image pixel per chip 8
+    // pixel in the palette's colours, otherwise the image as shown at
+    // the window's scale.
+    pub fn capture(&self, display: &Display, native: bool) -> Image {
+        if native {
+            return Image::from_display(display, &self.palette);
+        }
+        let image = self.image();
+        let width = display.width() * self.scale as usize;
+        image.scaled((width / image.width).max(1))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn display() -> Display {
+        let mut display = Display::new();
+        display.set_pixel(1, 0);
+        display
+    }
+
+    #[test]
+    fn native_capture_is_the_display_in_palette_colours() {
+        let video = Video::new(&Options::default());
+        let image = video.capture(&display(), true);
+        assert_eq!((image.width, image.height), (64, 32));
+        assert_eq!(image.get(0, 0), video.palette.color(0));
+        assert_eq!(image.get(1, 0), video.palette.color(1));
+    }
+
+    #[test]
+    fn scaled_capture_is_the_window_size() {
+        let options = Options::default();
+        let mut video = Video::new(&options);
+        let mut cpu = Cpu::new(&options);
+        cpu.display = display();
+        video.update(&cpu);
+        let image = video.capture(&cpu.display, false);
+        let scale = options.scale as usize;
+        assert_eq!((image.width, image.height), (64 * scale, 32 * scale));
+        assert_eq!(image.get(scale as isize, 0), video.palette.color(1));
+    }
+
+    #[test]
+    fn capture_before_the_first_frame_is_blank() {
+        let video = Video::new(&Options::default());
+        let image = video.capture(&display(), false);
+        assert!(image.width > 0);
+        assert!(image.pixels.iter().all(|p| *p == video.palette.color(0)));
+    }
+}